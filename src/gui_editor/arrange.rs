@@ -0,0 +1,199 @@
+//! Tidying up hand-placed nodes: the background grid, guides shown while dragging a node
+//! in line with another one, and aligning or spacing out the selection.
+
+use egui::emath::TSTransform;
+use egui::{Color32, Painter, Pos2, Rect, Stroke, Vec2};
+
+pub const GRID_SPACING: f32 = 25.0;
+/// Closer grid lines are skipped to keep the canvas readable when zoomed out
+const MIN_GRID_PIXELS: f32 = 10.0;
+const GRID_COLOR: Color32 = Color32::from_rgba_premultiplied(12, 12, 12, 12);
+const GUIDE_COLOR: Color32 = Color32::from_rgb(0xFF, 0x5C, 0xAA);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl Alignment {
+    pub const ALL: [Alignment; 6] = [
+        Alignment::Left,
+        Alignment::Center,
+        Alignment::Right,
+        Alignment::Top,
+        Alignment::Middle,
+        Alignment::Bottom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alignment::Left => "Align left",
+            Alignment::Center => "Align center",
+            Alignment::Right => "Align right",
+            Alignment::Top => "Align top",
+            Alignment::Middle => "Align middle",
+            Alignment::Bottom => "Align bottom",
+        }
+    }
+
+    /// The coordinate lined up and whether it is a y
+    fn edge(&self, rect: Rect) -> (f32, bool) {
+        match self {
+            Alignment::Left => (rect.left(), false),
+            Alignment::Center => (rect.center().x, false),
+            Alignment::Right => (rect.right(), false),
+            Alignment::Top => (rect.top(), true),
+            Alignment::Middle => (rect.center().y, true),
+            Alignment::Bottom => (rect.bottom(), true),
+        }
+    }
+}
+
+/// Lines a dragged node snapped to, in world coordinates
+#[derive(Default)]
+pub struct Guides {
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+}
+
+/// Edges and center along one axis
+fn features(rect: Rect, vertical: bool) -> [f32; 3] {
+    if vertical { [rect.top(), rect.center().y, rect.bottom()] } else { [rect.left(), rect.center().x, rect.right()] }
+}
+
+/// The smallest shift under `tolerance` lining up one of `own` with one of the others, and the line
+fn closest(own: [f32; 3], others: &[Rect], vertical: bool, tolerance: f32) -> Option<(f32, f32)> {
+    others.iter()
+        .flat_map(|r| features(*r, vertical))
+        .flat_map(|line| own.map(|f| (line - f, line)))
+        .filter(|(shift, _)| shift.abs() <= tolerance)
+        .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()))
+}
+
+/// Where a node dragged to `rect` lands: on the grid when `grid` is set, then in line with
+/// an edge or the center of one of `others` when `guides` is set and one is within `tolerance`
+pub fn snap(rect: Rect, others: &[Rect], grid: bool, guides: bool, tolerance: f32) -> (Pos2, Guides) {
+    let mut position = rect.min;
+    if grid { position = (position / GRID_SPACING).round() * GRID_SPACING; }
+    let mut found = Guides::default();
+    if guides {
+        if let Some((shift, line)) = closest(features(rect, false), others, false, tolerance) {
+            position.x = rect.min.x + shift;
+            found.x = Some(line);
+        }
+        if let Some((shift, line)) = closest(features(rect, true), others, true, tolerance) {
+            position.y = rect.min.y + shift;
+            found.y = Some(line);
+        }
+    }
+    (position, found)
+}
+
+/// How far each rect moves to line up with the first one on that side, the leftmost for
+/// `Left`, the lowest for `Bottom` and the average for centers
+pub fn align(rects: &[Rect], alignment: Alignment) -> Vec<Vec2> {
+    let edges: Vec<f32> = rects.iter().map(|r| alignment.edge(*r).0).collect();
+    let target = match alignment {
+        Alignment::Left | Alignment::Top => edges.iter().copied().reduce(f32::min),
+        Alignment::Right | Alignment::Bottom => edges.iter().copied().reduce(f32::max),
+        Alignment::Center | Alignment::Middle => Some(edges.iter().sum::<f32>() / edges.len().max(1) as f32),
+    };
+    let Some(target) = target else { return vec![] };
+    rects.iter().map(|r| match alignment.edge(*r) {
+        (edge, true) => Vec2::new(0.0, target - edge),
+        (edge, false) => Vec2::new(target - edge, 0.0),
+    }).collect()
+}
+
+/// How far each rect moves so that the centers are evenly spaced between the two outermost ones
+pub fn distribute(rects: &[Rect], vertical: bool) -> Vec<Vec2> {
+    let center = |r: &Rect| if vertical { r.center().y } else { r.center().x };
+    let mut order: Vec<usize> = (0..rects.len()).collect();
+    order.sort_by(|a, b| center(&rects[*a]).total_cmp(&center(&rects[*b])));
+    let mut deltas = vec![Vec2::ZERO; rects.len()];
+    let (Some(first), Some(last)) = (order.first(), order.last()) else { return deltas };
+    let (start, end) = (center(&rects[*first]), center(&rects[*last]));
+    let step = (end - start) / (rects.len().max(2) - 1) as f32;
+    for (i, r) in order.into_iter().enumerate() {
+        let shift = start + i as f32 * step - center(&rects[r]);
+        deltas[r] = if vertical { Vec2::new(0.0, shift) } else { Vec2::new(shift, 0.0) };
+    }
+    deltas
+}
+
+pub fn draw_grid(painter: &Painter, canvas: Rect, transform: TSTransform) {
+    let mut spacing = GRID_SPACING;
+    while spacing * transform.scaling < MIN_GRID_PIXELS { spacing *= 2.0; }
+    let world = transform.inverse() * canvas;
+    let stroke = Stroke::new(1.0, GRID_COLOR);
+    let mut x = (world.left() / spacing).ceil() * spacing;
+    while x < world.right() {
+        let x_screen = (transform * Pos2::new(x, 0.0)).x;
+        painter.vline(x_screen, canvas.y_range(), stroke);
+        x += spacing;
+    }
+    let mut y = (world.top() / spacing).ceil() * spacing;
+    while y < world.bottom() {
+        let y_screen = (transform * Pos2::new(0.0, y)).y;
+        painter.hline(canvas.x_range(), y_screen, stroke);
+        y += spacing;
+    }
+}
+
+pub fn draw_guides(painter: &Painter, canvas: Rect, transform: TSTransform, guides: &Guides) {
+    let stroke = Stroke::new(1.0, GUIDE_COLOR);
+    if let Some(x) = guides.x { painter.vline((transform * Pos2::new(x, 0.0)).x, canvas.y_range(), stroke); }
+    if let Some(y) = guides.y { painter.hline(canvas.x_range(), (transform * Pos2::new(0.0, y)).y, stroke); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(x: f32, y: f32) -> Rect {
+        Rect::from_min_size(Pos2::new(x, y), Vec2::splat(100.0))
+    }
+
+    #[test]
+    fn snapping_to_the_grid_and_to_other_nodes() {
+        let (position, guides) = snap(node(37.0, 61.0), &[], true, false, 10.0);
+        assert_eq!(position, Pos2::new(25.0, 50.0));
+        assert!(guides.x.is_none() && guides.y.is_none());
+
+        // Left edge 4 away from the other's right edge, centers 3 away vertically
+        let others = [node(200.0, 400.0)];
+        let (position, guides) = snap(node(304.0, 397.0), &others, false, true, 5.0);
+        assert_eq!(position, Pos2::new(300.0, 400.0));
+        assert_eq!((guides.x, guides.y), (Some(300.0), Some(400.0)));
+
+        // Guides win over the grid, and nothing snaps beyond the tolerance
+        let (position, guides) = snap(node(304.0, 430.0), &others, true, true, 5.0);
+        assert_eq!(position, Pos2::new(300.0, 425.0));
+        assert_eq!((guides.x, guides.y), (Some(300.0), None));
+    }
+
+    #[test]
+    fn aligning_moves_along_one_axis() {
+        let rects = [node(0.0, 0.0), node(60.0, 30.0), node(120.0, 90.0)];
+        assert_eq!(align(&rects, Alignment::Left), [Vec2::ZERO, Vec2::new(-60.0, 0.0), Vec2::new(-120.0, 0.0)]);
+        assert_eq!(align(&rects, Alignment::Bottom), [Vec2::new(0.0, 90.0), Vec2::new(0.0, 60.0), Vec2::ZERO]);
+        assert_eq!(align(&rects, Alignment::Center), [Vec2::new(60.0, 0.0), Vec2::ZERO, Vec2::new(-60.0, 0.0)]);
+        assert_eq!(align(&rects, Alignment::Middle), [Vec2::new(0.0, 40.0), Vec2::new(0.0, 10.0), Vec2::new(0.0, -50.0)]);
+        assert!(align(&[], Alignment::Top).is_empty());
+    }
+
+    #[test]
+    fn distributing_keeps_the_outermost_nodes() {
+        // Out of order on purpose, the one in the middle moves
+        let rects = [node(300.0, 0.0), node(0.0, 90.0), node(50.0, 30.0), node(200.0, 0.0)];
+        assert_eq!(distribute(&rects, false), [Vec2::ZERO, Vec2::ZERO, Vec2::new(50.0, 0.0), Vec2::ZERO]);
+        assert_eq!(distribute(&rects, true), [Vec2::ZERO, Vec2::ZERO, Vec2::new(0.0, 30.0), Vec2::new(0.0, 30.0)]);
+        assert_eq!(distribute(&rects[..1], false), [Vec2::ZERO]);
+        assert!(distribute(&[], true).is_empty());
+    }
+}
@@ -1,7 +1,8 @@
-use egui::{epaint::CubicBezierShape, Color32, Pos2};
+use egui::{emath::TSTransform, epaint::CubicBezierShape, Color32, Pos2};
 use super::drawable::Drawable;
 use super::TEXT_COLOR;
 
+#[derive(Clone)]
 pub struct Arrow {
     pub id: usize,
 
@@ -40,7 +41,8 @@ impl Arrow {
         self.labels.remove(index);
     }
 
-    pub fn is_near_curve(&self, pos: Pos2) -> bool {
+    /// `tolerance` is in world units, callers scale it by the zoom
+    pub fn is_near_curve(&self, pos: Pos2, tolerance: f32) -> bool {
         let off = self.get_control_offset();
         let (start, c1, c2, end) = (self.start, off.0, off.1, self.end);
 
@@ -49,7 +51,7 @@ impl Arrow {
         let min_y = start.y.min(c1.y).min(c2.y).min(end.y);
         let max_y = start.y.max(c1.y).max(c2.y).max(end.y);
 
-        if pos.x < min_x || pos.x > max_x || pos.y < min_y || pos.y > max_y {
+        if pos.x < min_x - tolerance || pos.x > max_x + tolerance || pos.y < min_y - tolerance || pos.y > max_y + tolerance {
             return false;
         }
         let steps = 6;
@@ -58,7 +60,7 @@ impl Arrow {
             let t = i as f32 / steps as f32;
             let pt = cubic_bezier_point(t, &[start, c1, c2, end]);
             let d = Arrow::distance_point_to_segment(pos, prev, pt);
-            if d < 6.0 { return true }
+            if d < tolerance { return true }
             prev = pt;
         }
         return false;
@@ -92,63 +94,80 @@ impl Arrow {
         let control2 = self.end - egui::vec2(h_offset, -v_offset);
         (control1, control2)
     }
+
+    pub fn point_at(&self, t: f32) -> Pos2 {
+        let (control1, control2) = self.get_control_offset();
+        cubic_bezier_point(t, &[self.start, control1, control2, self.end])
+    }
+
+    /// Center of the `index`-th label, labels are stacked under the middle of the curve
+    pub fn label_position(&self, index: usize) -> Pos2 {
+        self.point_at(0.5) + egui::vec2(0.0, 12.0 + index as f32 * 16.0)
+    }
+
+    pub fn tip_points(&self) -> Vec<Pos2> {
+        if self.id_to_node == Some(self.id_from_node) {
+            vec![
+                Pos2::new(self.end.x - 10.0, self.end.y + 0.5),
+                self.end,
+                Pos2::new(self.end.x - 1.0, self.end.y + 15.0),
+            ]
+        } else {
+            vec![
+                Pos2::new(self.end.x - 10.0, self.end.y + 10.0),
+                self.end,
+                Pos2::new(self.end.x - 10.0, self.end.y - 10.0),
+            ]
+        }
+    }
+
+    pub fn stroke(&self) -> egui::Stroke {
+        self.stroke
+    }
+
+    /// Draws the `index`-th label again in `color` with a box around it
+    pub fn highlight_label(&self, painter: &egui::Painter, transform: TSTransform, index: usize, color: Color32) {
+        let Some(label) = self.labels.get(index) else { return };
+        let rect = painter.text(
+            transform * self.label_position(index),
+            egui::Align2::CENTER_CENTER,
+            label,
+            egui::FontId::monospace(15.0 * transform.scaling),
+            color
+        );
+        painter.rect_stroke(rect.expand(2.0 * transform.scaling), 3.0 * transform.scaling, egui::Stroke::new(1.5, color), egui::StrokeKind::Outside);
+    }
 }
 
 impl Drawable for Arrow {
-    fn draw(&self, painter: &egui::Painter) {
+    fn draw(&self, painter: &egui::Painter, transform: TSTransform) {
         let (control1, control2) = self.get_control_offset();
+        let stroke = egui::Stroke::new(self.stroke.width * transform.scaling, self.stroke.color);
 
-        let (p0, p1, p2, p3) = (self.start, control1, control2, self.end);
-
+        let (p0, p1, p2, p3) = (transform * self.start, transform * control1, transform * control2, transform * self.end);
         let bezier = egui::Shape::CubicBezier(
             CubicBezierShape {
                 points: [p0, p1, p2, p3],
                 closed: false,
                 fill: Color32::TRANSPARENT,
-                stroke: self.stroke.into()
+                stroke: stroke.into()
             }
         );
         painter.add(bezier);
 
-        let start_label_position = cubic_bezier_point(0.5, &[p0, p1, p2, p3]) + egui::vec2(0.0, 12.0);
-
         for (i, label) in self.labels.iter().enumerate() {
             painter.text(
-                start_label_position + egui::vec2(0.0,i as f32 * 16.0),
+                transform * self.label_position(i),
                 egui::Align2::CENTER_CENTER,
                 label,
-                egui::FontId::monospace(15.0),
+                egui::FontId::monospace(15.0 * transform.scaling),
                 TEXT_COLOR
             );
         }
 
-        let tip_points = {
-            if let Some(to) = self.id_to_node {
-                if to == self.id_from_node {
-                    vec![
-                        Pos2::new(self.end.x - 10.0, self.end.y + 0.5),
-                        self.end,
-                        Pos2::new(self.end.x - 1.0, self.end.y + 15.0),
-                    ]
-                } else {
-                    vec![
-                        Pos2::new(self.end.x - 10.0, self.end.y + 10.0),
-                        self.end,
-                        Pos2::new(self.end.x - 10.0, self.end.y - 10.0),
-                    ]  
-                }
-            } else {
-                vec![
-                    Pos2::new(self.end.x - 10.0, self.end.y + 10.0),
-                    self.end,
-                    Pos2::new(self.end.x - 10.0, self.end.y - 10.0),
-                ]
-            }
-        };
-        
         painter.add(egui::Shape::line(
-            tip_points,
-            self.stroke
+            self.tip_points().into_iter().map(|p| transform * p).collect(),
+            stroke
         ));
     }
 }
@@ -172,22 +191,6 @@ fn cubic_bezier_point(t: f32, p: &[Pos2; 4]) -> Pos2 {
     Pos2::new(vec.x, vec.y)
 }
 
-impl super::Serializable for Arrow {
-    fn serialize(&self) -> String {
-        let mut x = format!("{}, {}, {}, {}, {}, [", 
-            self.id, 
-            self.start, 
-            self.end, 
-            self.id_from_node, match 
-            self.id_to_node { None => { String::from("none") } Some(id) => { format!("{}", id) } });
-        for (i, label) in self.labels.iter().enumerate() {
-            x.push_str(label.as_str());
-            if i < self.labels.len() - 1 { x.push_str(", ") }
-        }
-        x.push(']');
-        x
-    }
-}
 impl super::Deserializable for Arrow {
     fn deserialize(from: &String) -> Result<Self, String> where Self: Sized {
         let pieces: Vec<&str> = from.split(", ").collect();
@@ -199,16 +202,16 @@ impl super::Deserializable for Arrow {
         let start_str = pieces.get(1)
             .ok_or("No start")?
             .trim_matches(|c| c == '[' || c == ']');
-        let start_vec: Vec<u32> = start_str.split_whitespace()
-            .map(|n| n.parse::<u32>()
+        let start_vec: Vec<f32> = start_str.split_whitespace()
+            .map(|n| n.parse::<f32>()
             .map_err(|_| "Could not parse start".to_owned()))
             .collect::<Result<Vec<_>, _>>()?;
         
         let end_str = pieces.get(2)
             .ok_or("No end")?
             .trim_matches(|c| c == '[' || c == ']');
-        let end_vec: Vec<u32> = end_str.split_whitespace()
-            .map(|n| n.parse::<u32>()
+        let end_vec: Vec<f32> = end_str.split_whitespace()
+            .map(|n| n.parse::<f32>()
             .map_err(|_| "Could not parse end".to_owned()))
             .collect::<Result<Vec<_>, _>>()?;
         
@@ -222,19 +225,16 @@ impl super::Deserializable for Arrow {
             else { Some(x.parse().map_err(|_| String::from("Could not parse to id"))?) }
         };
 
-        let mut labels = Vec::<String>::with_capacity(pieces.len() - 5);
-        for i in 5..pieces.len() {
-            let label = pieces.get(i)
-                .ok_or("No labels")?
-                .trim_matches(|c| c == '[' || c == ']');
-            labels.push(label.to_owned());
-        }
-        
+        if start_vec.len() != 2 || end_vec.len() != 2 { return Err(String::from("Positions need two coordinates")) }
+
+        let list = pieces.get(5..).ok_or("No labels")?.join(", ");
+        let list = list.strip_prefix('[').and_then(|l| l.strip_suffix(']')).ok_or("Labels must be between [ and ]")?;
+        let labels: Vec<String> = if list.is_empty() { vec![] } else { list.split(", ").map(|l| l.to_owned()).collect() };
 
         let mut arrow = Arrow::new(
             id, 
-            Pos2::new(start_vec[0] as f32, start_vec[1] as f32), 
-            Pos2::new(end_vec[0] as f32, end_vec[1] as f32),
+            Pos2::new(start_vec[0], start_vec[1]), 
+            Pos2::new(end_vec[0], end_vec[1]),
             from_id,
             to_id
         );
@@ -0,0 +1,105 @@
+//! Copied nodes go through the system clipboard as a project file holding only them and the
+//! arrows between them, so they can be pasted in another editor or saved as a project.
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::arrow::Arrow;
+use super::node::Node;
+use super::project::Project;
+
+/// Nodes picked from the diagram with the arrows that stay inside them, ids are the original ones
+pub fn collect(nodes: &[Option<Node>], arrows: &[Option<Arrow>], ids: &BTreeSet<usize>) -> (Vec<Node>, Vec<Arrow>) {
+    let picked = nodes.iter().flatten().filter(|n| ids.contains(&n.id)).cloned().collect();
+    let inside = arrows.iter().flatten()
+        .filter(|a| ids.contains(&a.id_from_node) && a.id_to_node.is_some_and(|to| ids.contains(&to)))
+        .cloned()
+        .collect();
+    (picked, inside)
+}
+
+/// Ids are packed from 0 so the text is also a valid project
+pub fn to_text(nodes: &[Node], arrows: &[Arrow]) -> Result<String, String> {
+    let new_ids: HashMap<usize, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+    let nodes: Vec<Option<Node>> = nodes.iter().enumerate().map(|(i, n)| {
+        let mut node = n.clone();
+        node.id = i;
+        Some(node)
+    }).collect();
+    let arrows: Vec<Option<Arrow>> = arrows.iter().filter_map(|a| {
+        let mut arrow = a.clone();
+        arrow.id_from_node = *new_ids.get(&a.id_from_node)?;
+        arrow.id_to_node = Some(*new_ids.get(&a.id_to_node?)?);
+        Some(arrow)
+    }).enumerate().map(|(i, mut arrow)| {
+        arrow.id = i;
+        Some(arrow)
+    }).collect();
+    Project::new(&nodes, &arrows).to_json()
+}
+
+/// `None` when the text is not a project the editor could load as it is
+pub fn from_text(text: &str) -> Option<(Vec<Node>, Vec<Arrow>)> {
+    let project = Project::parse(text).ok()?;
+    if !project.problems().is_empty() { return None }
+    let (nodes, arrows) = project.diagram();
+    Some((nodes.into_iter().flatten().collect(), arrows.into_iter().flatten().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{Color32, Pos2};
+
+    fn diagram() -> (Vec<Option<Node>>, Vec<Option<Arrow>>) {
+        let node = |id: usize, x: f32| Some(Node::new(id, format!("q{}", id), Pos2::new(x, 0.0), Color32::WHITE, true));
+        let arrow = |id: usize, from: usize, to: Option<usize>, label: &str| {
+            let mut arrow = Arrow::new(id, Pos2::ZERO, Pos2::ZERO, from, to);
+            arrow.add_label(label.to_owned());
+            Some(arrow)
+        };
+        let nodes = vec![node(0, 0.0), None, node(2, 200.0), node(3, 400.0), node(4, 600.0)];
+        let arrows = vec![
+            arrow(0, 2, Some(4), "a/R"),
+            arrow(1, 0, Some(2), "b/L"),
+            arrow(2, 4, Some(3), "a/b"),
+            arrow(3, 4, None, ""),
+            arrow(4, 4, Some(4), "b/R"),
+        ];
+        (nodes, arrows)
+    }
+
+    #[test]
+    fn ids_are_packed_when_pasted() {
+        let (nodes, arrows) = diagram();
+        let (picked, inside) = collect(&nodes, &arrows, &BTreeSet::from([2, 4]));
+        let (pasted_nodes, pasted_arrows) = from_text(&to_text(&picked, &inside).unwrap()).unwrap();
+
+        let labels: Vec<(usize, &str)> = pasted_nodes.iter().map(|n| (n.id, n.label.as_str())).collect();
+        assert_eq!(labels, [(0, "q2"), (1, "q4")]);
+        let links: Vec<(usize, usize, Option<usize>, &str)> = pasted_arrows.iter()
+            .map(|a| (a.id, a.id_from_node, a.id_to_node, a.labels[0].as_str()))
+            .collect();
+        assert_eq!(links, [(0, 0, Some(1), "a/R"), (1, 1, Some(1), "b/R")]);
+    }
+
+    #[test]
+    fn arrows_leaving_the_selection_are_dropped() {
+        let (nodes, arrows) = diagram();
+        let (picked, inside) = collect(&nodes, &arrows, &BTreeSet::from([4]));
+        assert_eq!(picked.len(), 1);
+        // Only the loop stays, not the arrows from 2, to 3 or the one being drawn
+        assert_eq!(inside.iter().map(|a| a.id).collect::<Vec<_>>(), [4]);
+
+        // Arrows handed over without their nodes are skipped too
+        let stray: Vec<Arrow> = arrows.iter().flatten().cloned().collect();
+        let (_, pasted_arrows) = from_text(&to_text(&picked, &stray).unwrap()).unwrap();
+        assert_eq!(pasted_arrows.len(), 1);
+        assert_eq!((pasted_arrows[0].id_from_node, pasted_arrows[0].id_to_node), (0, Some(0)));
+    }
+
+    #[test]
+    fn other_text_is_not_pasted() {
+        assert!(from_text("hello").is_none());
+        assert!(from_text("").is_none());
+    }
+}
@@ -1,5 +1,7 @@
+use egui::emath::TSTransform;
 use egui::Painter;
 
 pub trait Drawable {
-    fn draw(&self, painter: &Painter);
+    /// Paints in screen coordinates, `transform` goes from the world to the screen
+    fn draw(&self, painter: &Painter, transform: TSTransform);
 }
\ No newline at end of file
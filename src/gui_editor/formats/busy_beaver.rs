@@ -0,0 +1,120 @@
+//! Busy beaver standard notation like `1RB1LB_1LA1RZ`, going through `TuringMachine<1>`.
+//!
+//! Symbols are the digits with `0` as the blank and the halting state becomes a final
+//! node named Z. Writes and moves are separate steps here, so every write gets a state
+//! named after its target and direction that only moves.
+
+use crate::turing::busy_beaver::{state_letter, Table, HALT};
+use crate::turing::{LSymbol, State, TuringMachine};
+
+use super::{make_label, split_label, Description, ImportResult, ParseError, TransitionDescription, RESERVED_SYMBOLS};
+
+fn digit(x: LSymbol) -> char {
+    char::from_digit(x as u32, 10).unwrap_or('?')
+}
+
+pub fn parse(source: &str) -> ImportResult {
+    let table = Table::parse(source).map_err(|e| ParseError::general(&e))?;
+    let m = table.to_machine().map_err(|e| ParseError::general(&e))?;
+    let n = table.states.len();
+
+    let mut transitions: Vec<(State, LSymbol, LSymbol, State)> = m.transitions_reference().iter()
+        .map(|((q, [x]), (t, [a]))| (*q, *x, *a, *t))
+        .collect();
+    transitions.sort();
+    let action = |a: LSymbol| if a == m.dx() { 'R' } else if a == m.sx() { 'L' } else { digit(a) };
+
+    let mut description = Description::new(1, '0');
+    for q in 0..m.state_count() as usize {
+        let is_final = m.is_final_state(q as State).unwrap_or(false);
+        let name = if q < n {
+            state_letter(q).to_string()
+        } else if is_final {
+            HALT.to_string()
+        } else {
+            let (_, _, a, t) = transitions.iter().find(|t| t.0 as usize == q).copied().unwrap_or_default();
+            let letter = if t as usize == n { HALT } else { state_letter(t as usize) };
+            format!("{}~{}", letter, action(a))
+        };
+        description.add_state(name, None, is_final);
+    }
+    for (q, x, a, t) in transitions {
+        let label = make_label(&[digit(x)], &[action(a)]);
+        description.transitions.push(TransitionDescription { from: q as usize, to: t as usize, label });
+    }
+    Ok((description, vec![]))
+}
+
+/// Digits are kept when the blank is `0`, otherwise the blank becomes 0 and the
+/// other symbols follow in order of appearance
+pub fn print(description: &Description) -> Result<String, String> {
+    if description.tapes != 1 { return Err("The notation only describes single tape machines".to_owned()) }
+    let names = description.unique_names();
+
+    let mut labels = vec![];
+    for t in &description.transitions {
+        let (read, action) = split_label(&t.label, 1)?;
+        labels.push((t.from, read[0], action[0], t.to));
+    }
+    let mut symbols = vec![description.blank];
+    for (_, read, action, _) in &labels {
+        for c in [read, action] {
+            if !RESERVED_SYMBOLS.contains(c) && !symbols.contains(c) { symbols.push(*c); }
+        }
+    }
+    let digits = description.blank == '0' && symbols.iter().all(|c| c.is_ascii_digit());
+    let value_of = |c: char| if digits {
+        c.to_digit(10).unwrap_or(0) as LSymbol
+    } else {
+        symbols.iter().position(|s| *s == c).unwrap_or(0) as LSymbol
+    };
+    let symbol_count = symbols.iter().map(|c| value_of(*c) as usize + 1).max().unwrap_or(1);
+    if symbol_count > 10 { return Err(format!("{} symbols, at most 10 fit in the notation", symbol_count)) }
+
+    let mut m = TuringMachine::<1>::new(description.states.len(), symbol_count).map_err(|e| format!("{:?}", e))?;
+    for (q, state) in description.states.iter().enumerate() {
+        if state.is_final { m.add_final_state(q as State).ok(); }
+    }
+    for (q, read, action, t) in labels {
+        let a = match action { 'L' => m.sx(), 'R' => m.dx(), c => value_of(c) };
+        m.add_transition(q as State, [value_of(read)], [a], t as State)
+            .map_err(|_| format!("{} has more than one transition on {}", names[q], read))?;
+    }
+    Table::from_machine(&m).map(|table| table.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for notation in ["1RB1LB_1LA1RZ", "1RB1LB_1LA1LZ", "1RB1LB_1LZ1LA", "1RB---_0LC1RZ_1LC1LA"] {
+            let (description, _) = parse(notation).unwrap();
+            assert_eq!(description.states[0].name, "A");
+            assert_eq!(print(&description).unwrap(), notation);
+        }
+    }
+
+    #[test]
+    fn halting_entries_keep_their_direction() {
+        let (right, _) = parse("1RB1LB_1RZ1LA").unwrap();
+        let (left, _) = parse("1RB1LB_1LZ1LA").unwrap();
+        // B writes 1 on 0 and halts, the move happens in a state on the way to Z
+        let names: Vec<&str> = left.states.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "Z", "B~R", "Z~L"]);
+        assert!(left.states[2].is_final);
+        let halting = |d: &Description| d.transitions.iter().find(|t| t.to == 2).map(|t| t.label.clone());
+        assert_eq!(halting(&left).as_deref(), Some("0/L"));
+        assert_eq!(halting(&right).as_deref(), Some("0/R"));
+    }
+
+    #[test]
+    fn malformed() {
+        let missing_state = parse("1RC1LB_1LA1RZ").err().expect("C is not in a 2 state table");
+        assert!(missing_state.message.contains("state C is not in the table"), "{}", missing_state);
+        assert!(parse("1RB1LB_1LA").is_err());
+        assert!(parse("2RB1LB_1LA1RZ").is_err());
+        assert!(parse("1UB1LB_1LA1RZ").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Graphviz DOT export, one edge per arrow with all of its labels.
+//!
+//! A run can be highlighted, the states it went through and the transitions it fired
+//! are drawn thicker and in the final color, fired labels are also bold.
+
+use std::collections::HashSet;
+
+use crate::turing::label;
+
+use super::Description;
+
+const HIGHLIGHT: &str = "#F02C2C";
+
+/// What a computation went through, with states as indices in the description and
+/// transitions as their source state and the symbols read, joined like in labels
+#[derive(Default)]
+pub struct Run {
+    pub states: HashSet<usize>,
+    pub transitions: HashSet<(usize, String)>,
+}
+
+/// Pair of states with its labels and whether the run fired them
+type Edge<'a> = ((usize, usize), Vec<(&'a str, bool)>);
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn print(description: &Description, run: Option<&Run>) -> String {
+    let names = description.unique_names();
+    let mut out = String::from("digraph machine {\n");
+    out.push_str("    rankdir=LR;\n");
+    out.push_str("    node [shape=circle];\n");
+    out.push_str("    start [shape=point, label=\"\"];\n");
+    if !description.states.is_empty() { out.push_str("    start -> s0;\n"); }
+
+    for (i, state) in description.states.iter().enumerate() {
+        let mut attributes = vec![format!("label={}", quote(&names[i]))];
+        if state.is_final { attributes.push("shape=doublecircle".to_owned()); }
+        if run.is_some_and(|r| r.states.contains(&i)) {
+            attributes.push(format!("color={}", quote(HIGHLIGHT)));
+            attributes.push("penwidth=2.5".to_owned());
+        }
+        out.push_str(&format!("    s{} [{}];\n", i, attributes.join(", ")));
+    }
+
+    // Labels grouped per pair of states like the arrows in the editor
+    let mut edges: Vec<Edge> = vec![];
+    for t in &description.transitions {
+        let fired = run.is_some_and(|r| {
+            label::split(&t.label, description.tapes as usize)
+                .is_ok_and(|(read, _)| r.transitions.contains(&(t.from, label::join(&read))))
+        });
+        match edges.iter_mut().find(|(pair, _)| *pair == (t.from, t.to)) {
+            Some((_, labels)) => labels.push((&t.label, fired)),
+            None => edges.push(((t.from, t.to), vec![(&t.label, fired)])),
+        }
+    }
+    for ((from, to), labels) in edges {
+        let mut attributes = vec![];
+        if labels.iter().any(|(_, fired)| *fired) {
+            let lines: Vec<String> = labels.iter().map(|(label, fired)| {
+                if *fired { format!("<b>{}</b>", escape_html(label)) } else { escape_html(label) }
+            }).collect();
+            attributes.push(format!("label=<{}>", lines.join("<br/>")));
+            attributes.push(format!("color={}", quote(HIGHLIGHT)));
+            attributes.push(format!("fontcolor={}", quote(HIGHLIGHT)));
+            attributes.push("penwidth=2.5".to_owned());
+        } else {
+            let text: Vec<&str> = labels.iter().map(|(label, _)| *label).collect();
+            attributes.push(format!("label={}", quote(&text.join("\n")).replace('\n', "\\n")));
+        }
+        out.push_str(&format!("    s{} -> s{} [{}];\n", from, to, attributes.join(", ")));
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::TransitionDescription;
+
+    fn machine() -> Description {
+        let mut description = Description::new(1, '*');
+        let a = description.add_state("say \"hi\\\"".to_owned(), None, false);
+        let b = description.add_state("b".to_owned(), None, true);
+        for (from, label, to) in [(a, "1/R", b), (a, "0/1", b), (b, "</L", b)] {
+            description.transitions.push(TransitionDescription { from, to, label: label.to_owned() });
+        }
+        description
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(print(&machine(), None), "\
+digraph machine {
+    rankdir=LR;
+    node [shape=circle];
+    start [shape=point, label=\"\"];
+    start -> s0;
+    s0 [label=\"say \\\"hi\\\\\\\"\"];
+    s1 [label=\"b\", shape=doublecircle];
+    s0 -> s1 [label=\"1/R\\n0/1\"];
+    s1 -> s1 [label=\"</L\"];
+}
+");
+    }
+
+    #[test]
+    fn highlighted_run() {
+        let run = Run {
+            states: HashSet::from([0, 1]),
+            transitions: HashSet::from([(0, "0".to_owned()), (1, "<".to_owned())]),
+        };
+        let printed = print(&machine(), Some(&run));
+        assert!(printed.contains("s1 [label=\"b\", shape=doublecircle, color=\"#F02C2C\", penwidth=2.5];"), "{}", printed);
+        assert!(printed.contains("s0 -> s1 [label=<1/R<br/><b>0/1</b>>, color=\"#F02C2C\", fontcolor=\"#F02C2C\", penwidth=2.5];"), "{}", printed);
+        assert!(printed.contains("s1 -> s1 [label=<<b>&lt;/L</b>>,"), "{}", printed);
+
+        // Without anything fired the edges stay plain
+        let printed = print(&machine(), Some(&Run::default()));
+        assert!(printed.contains("s0 [label=\"say \\\"hi\\\\\\\"\"];"), "{}", printed);
+        assert!(printed.contains("s0 -> s1 [label=\"1/R\\n0/1\"];"), "{}", printed);
+
+        // Symbols read on several tapes are matched the way labels join them
+        let mut description = Description::new(2, '*');
+        let a = description.add_state("a".to_owned(), None, false);
+        for label in ["x1,a/R,R", "ab/LL"] {
+            description.transitions.push(TransitionDescription { from: a, to: a, label: label.to_owned() });
+        }
+        let run = Run { states: HashSet::from([a]), transitions: HashSet::from([(a, "x1,a".to_owned())]) };
+        assert!(print(&description, Some(&run)).contains("[label=<<b>x1,a/R,R</b><br/>ab/LL>,"));
+    }
+}
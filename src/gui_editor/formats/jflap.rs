@@ -0,0 +1,428 @@
+//! JFLAP `.jff` Turing machines, single and multi tape, in both directions.
+//!
+//! JFLAP stores the centre of each state in its own pixel space, which is scaled to fit nodes here.
+//! An empty `read`/`write` is the blank symbol and `~` matches any symbol / keeps the one read.
+
+use egui::Pos2;
+use std::collections::{BTreeSet, HashMap};
+
+use super::{combinations, Description, ImportResult, Move, ParseError, NODE_HALF_SIZE, RESERVED_SYMBOLS};
+
+pub const SCALE: f32 = 2.5;
+const BLANK_CANDIDATES: [char; 5] = ['*', '_', '#', '□', '$'];
+const WILDCARD: &str = "~";
+
+struct JflapTransition {
+    from: String,
+    to: String,
+    read: Vec<String>,
+    write: Vec<String>,
+    moves: Vec<String>,
+    line: usize,
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, 'a>, name: &str) -> Option<&'a str> {
+    node.children().find(|c| c.has_tag_name(name)).map(|c| c.text().unwrap_or("").trim())
+}
+
+/// Text of the `name` children, ordered by their `tape` attribute (missing means tape 1)
+fn per_tape(node: roxmltree::Node, name: &str, tapes: usize) -> Result<Vec<String>, String> {
+    let mut values = vec![None; tapes];
+    for child in node.children().filter(|c| c.has_tag_name(name)) {
+        let tape: usize = child.attribute("tape").unwrap_or("1").parse().map_err(|_| format!("Bad tape number in <{}>", name))?;
+        if tape == 0 || tape > tapes { return Err(format!("<{}> refers to tape {} of {}", name, tape, tapes)) }
+        values[tape - 1] = Some(child.text().unwrap_or("").trim().to_owned());
+    }
+    values.into_iter().enumerate()
+        .map(|(i, v)| v.ok_or(format!("Missing <{}> for tape {}", name, i + 1)))
+        .collect()
+}
+
+pub fn parse(source: &str) -> ImportResult {
+    let document = roxmltree::Document::parse(source).map_err(|e| {
+        ParseError::at(e.pos().row as usize, format!("Invalid XML, {}", e))
+    })?;
+    let line_of = |node: roxmltree::Node| document.text_pos_at(node.range().start).row as usize;
+    let mut warnings = vec![];
+
+    let structure = document.root_element();
+    if !structure.has_tag_name("structure") { return Err(ParseError::general("Not a JFLAP file, <structure> is missing")) }
+    match child_text(structure, "type") {
+        Some("turing") => {}
+        Some(other) => return Err(ParseError::general(&format!("Only Turing machines can be imported, this is a '{}'", other))),
+        None => return Err(ParseError::general("The machine type is missing")),
+    }
+    let tapes: usize = match child_text(structure, "tapes") {
+        None => 1,
+        Some(t) => t.parse().map_err(|_| ParseError::general(&format!("Could not parse tape count '{}'", t)))?,
+    };
+    if !(1..=3).contains(&tapes) {
+        return Err(ParseError::general(&format!("{} tapes, only 1 to 3 are supported", tapes)))
+    }
+    let automaton = structure.children().find(|c| c.has_tag_name("automaton")).unwrap_or(structure);
+
+    // States, keeping the initial one first
+    let mut states = vec![];
+    let mut initial = None;
+    for element in automaton.children().filter(|c| c.is_element()) {
+        match element.tag_name().name() {
+            "state" => {
+                let line = line_of(element);
+                let id = element.attribute("id").ok_or(ParseError::at(line, "State without an id".to_owned()))?.to_owned();
+                let name = element.attribute("name").map(|n| n.to_owned()).unwrap_or_else(|| format!("q{}", id));
+                let coordinate = |axis: &str| child_text(element, axis).and_then(|v| v.parse::<f32>().ok());
+                let position = match (coordinate("x"), coordinate("y")) {
+                    (Some(x), Some(y)) => Some(Pos2::new(x * SCALE - NODE_HALF_SIZE, y * SCALE - NODE_HALF_SIZE)),
+                    _ => { warnings.push(format!("Line {}: state {} has no position, it will be laid out", line, name)); None }
+                };
+                let is_final = element.children().any(|c| c.has_tag_name("final"));
+                if element.children().any(|c| c.has_tag_name("initial")) {
+                    if initial.is_some() {
+                        warnings.push(format!("Line {}: {} is a second initial state, only the first one is kept", line, name));
+                    } else {
+                        initial = Some(states.len());
+                    }
+                }
+                states.push((id, name, position, is_final));
+            }
+            "block" => warnings.push(format!("Line {}: building blocks are not supported, the block was skipped", line_of(element))),
+            "note" => warnings.push(format!("Line {}: notes are not supported and were dropped", line_of(element))),
+            "transition" => {}
+            other => warnings.push(format!("Line {}: unknown element <{}> was ignored", line_of(element), other)),
+        }
+    }
+    if states.is_empty() { return Err(ParseError::general("The machine has no states")) }
+    let initial = initial.unwrap_or_else(|| {
+        warnings.push(format!("No initial state, {} will be used", states[0].1));
+        0
+    });
+    let first = states.remove(initial);
+    states.insert(0, first);
+
+    let mut transitions = vec![];
+    for element in automaton.children().filter(|c| c.has_tag_name("transition")) {
+        let line = line_of(element);
+        let at = |e: String| ParseError::at(line, e);
+        transitions.push(JflapTransition {
+            from: child_text(element, "from").ok_or_else(|| at("Transition without <from>".to_owned()))?.to_owned(),
+            to: child_text(element, "to").ok_or_else(|| at("Transition without <to>".to_owned()))?.to_owned(),
+            read: per_tape(element, "read", tapes).map_err(at)?,
+            write: per_tape(element, "write", tapes).map_err(at)?,
+            moves: per_tape(element, "move", tapes).map_err(at)?,
+            line,
+        });
+    }
+
+    // Alphabet, blank is whatever is free
+    let mut alphabet = BTreeSet::<char>::new();
+    for t in &transitions {
+        for symbol in t.read.iter().chain(t.write.iter()) {
+            if symbol != WILDCARD { alphabet.extend(symbol.chars()); }
+        }
+    }
+    let blank = *BLANK_CANDIDATES.iter().find(|c| !alphabet.contains(c))
+        .ok_or(ParseError::general("Could not find a free blank symbol"))?;
+    let mut symbols: Vec<char> = alphabet.iter().copied().filter(|c| !RESERVED_SYMBOLS.contains(c)).collect();
+    symbols.push(blank);
+    for c in alphabet.iter().filter(|c| RESERVED_SYMBOLS.contains(c)) {
+        warnings.push(format!("Symbol {} is reserved in labels, transitions using it were dropped", c));
+    }
+
+    let mut description = Description::new(tapes as u8, blank);
+    let mut index_of = HashMap::<String, usize>::new();
+    for (id, name, position, is_final) in states {
+        index_of.insert(id, description.add_state(name, position, is_final));
+    }
+
+    description.move_to_origin();
+
+    let mut intermediate = HashMap::new();
+    let mut seen = std::collections::HashSet::<(usize, Vec<char>)>::new();
+    'transitions: for t in transitions {
+        let at = |e: String| ParseError::at(t.line, e);
+        let from = *index_of.get(&t.from).ok_or_else(|| at(format!("Unknown state id {}", t.from)))?;
+        let to = *index_of.get(&t.to).ok_or_else(|| at(format!("Unknown state id {}", t.to)))?;
+
+        let mut read_options = vec![];
+        for symbol in &t.read {
+            read_options.push(match symbol.as_str() {
+                "" => vec![blank],
+                WILDCARD => symbols.clone(),
+                s if s.chars().count() == 1 => vec![s.chars().next().unwrap()],
+                s => {
+                    warnings.push(format!("Line {}: multi symbol read '{}' is not supported, transition dropped", t.line, s));
+                    continue 'transitions;
+                }
+            });
+        }
+        let mut moves = vec![];
+        for m in &t.moves {
+            moves.push(match m.as_str() {
+                "L" => Move::Left,
+                "R" => Move::Right,
+                "S" => Move::Stay,
+                other => return Err(at(format!("Unknown move '{}'", other))),
+            });
+        }
+
+        for read in combinations(&read_options) {
+            if read.iter().any(|c| RESERVED_SYMBOLS.contains(c)) { continue 'transitions }
+            let mut write = vec![];
+            for (i, symbol) in t.write.iter().enumerate() {
+                write.push(match symbol.as_str() {
+                    "" => blank,
+                    WILDCARD => read[i],
+                    s if s.chars().count() == 1 => s.chars().next().unwrap(),
+                    s => {
+                        warnings.push(format!("Line {}: multi symbol write '{}' is not supported, transition dropped", t.line, s));
+                        continue 'transitions;
+                    }
+                });
+            }
+            if write.iter().any(|c| RESERVED_SYMBOLS.contains(c)) { continue 'transitions }
+            if !seen.insert((from, read.clone())) {
+                warnings.push(format!("Line {}: nondeterministic transition on {} was dropped", t.line, read.iter().collect::<String>()));
+                continue;
+            }
+            description.add_quintuple(&mut intermediate, from, &read, &write, &moves, to);
+        }
+    }
+    if description.states.len() > 256 {
+        return Err(ParseError::general(&format!("{} states are needed, at most 256 are supported", description.states.len())))
+    }
+
+    Ok((description, warnings))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+fn tag(name: &str, tape: Option<usize>, value: &str) -> String {
+    let attribute = tape.map(|t| format!(" tape=\"{}\"", t)).unwrap_or_default();
+    if value.is_empty() {
+        format!("<{}{}/>", name, attribute)
+    } else {
+        format!("<{}{}>{}</{}>", name, attribute, escape(value), name)
+    }
+}
+
+pub fn print(description: &Description) -> Result<String, String> {
+    let quintuples = description.quintuples()?;
+    let names = description.unique_names();
+    let tapes = description.tapes as usize;
+    let symbol = |c: char| if c == description.blank { String::new() } else { c.to_string() };
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n<structure>\n");
+    out.push_str("\t<type>turing</type>\n");
+    if tapes > 1 { out.push_str(&format!("\t<tapes>{}</tapes>\n", tapes)); }
+    out.push_str("\t<automaton>\n");
+
+    for (i, state) in description.states.iter().enumerate() {
+        let position = state.position.unwrap_or(egui::Pos2::ZERO);
+        out.push_str(&format!("\t\t<state id=\"{}\" name=\"{}\">\n", i, escape(&names[i])));
+        out.push_str(&format!("\t\t\t<x>{:.1}</x>\n", (position.x + NODE_HALF_SIZE) / SCALE));
+        out.push_str(&format!("\t\t\t<y>{:.1}</y>\n", (position.y + NODE_HALF_SIZE) / SCALE));
+        if i == 0 { out.push_str("\t\t\t<initial/>\n"); }
+        if state.is_final { out.push_str("\t\t\t<final/>\n"); }
+        out.push_str("\t\t</state>\n");
+    }
+
+    for q in quintuples {
+        out.push_str("\t\t<transition>\n");
+        out.push_str(&format!("\t\t\t<from>{}</from>\n", q.from));
+        out.push_str(&format!("\t\t\t<to>{}</to>\n", q.to));
+        let tape = |i: usize| if tapes > 1 { Some(i + 1) } else { None };
+        for i in 0..tapes { out.push_str(&format!("\t\t\t{}\n", tag("read", tape(i), &symbol(q.read[i])))); }
+        for i in 0..tapes { out.push_str(&format!("\t\t\t{}\n", tag("write", tape(i), &symbol(q.write[i])))); }
+        for i in 0..tapes {
+            let m = match q.moves[i] { Move::Left => "L", Move::Right => "R", Move::Stay => "S" };
+            out.push_str(&format!("\t\t\t{}\n", tag("move", tape(i), m)));
+        }
+        out.push_str("\t\t</transition>\n");
+    }
+
+    out.push_str("\t</automaton>\n</structure>\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// As saved by JFLAP 7.1, empty tags are the blank and `~` leaves the symbol alone
+    const SINGLE_TAPE: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?><!--Created with JFLAP 7.1.--><structure>&#13;
+	<type>turing</type>&#13;
+	<automaton>&#13;
+		<!--The list of states.-->&#13;
+		<state id="0" name="q0">&#13;
+			<x>84.0</x>&#13;
+			<y>105.0</y>&#13;
+			<initial/>&#13;
+		</state>&#13;
+		<state id="1" name="q1">&#13;
+			<x>231.0</x>&#13;
+			<y>105.0</y>&#13;
+			<final/>&#13;
+		</state>&#13;
+		<!--The list of transitions.-->&#13;
+		<transition>&#13;
+			<from>0</from>&#13;
+			<to>0</to>&#13;
+			<read>a</read>&#13;
+			<write>b</write>&#13;
+			<move>S</move>&#13;
+		</transition>&#13;
+		<transition>&#13;
+			<from>0</from>&#13;
+			<to>0</to>&#13;
+			<read>b</read>&#13;
+			<write/>&#13;
+			<move>S</move>&#13;
+		</transition>&#13;
+		<transition>&#13;
+			<from>0</from>&#13;
+			<to>1</to>&#13;
+			<read/>&#13;
+			<write>1</write>&#13;
+			<move>S</move>&#13;
+		</transition>&#13;
+		<transition>&#13;
+			<from>1</from>&#13;
+			<to>1</to>&#13;
+			<read>~</read>&#13;
+			<write>~</write>&#13;
+			<move>L</move>&#13;
+		</transition>&#13;
+	</automaton>&#13;
+</structure>"#;
+
+    /// Multi tape machines have a `read`, `write` and `move` per tape, in any order
+    const TWO_TAPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?><!--Created with JFLAP 7.1.--><structure>&#13;
+	<type>turing</type>&#13;
+	<tapes>2</tapes>&#13;
+	<automaton>&#13;
+		<state id="3" name="copy">&#13;
+			<x>60.0</x>&#13;
+			<y>80.0</y>&#13;
+			<initial/>&#13;
+		</state>&#13;
+		<state id="7" name="end">&#13;
+			<x>200.0</x>&#13;
+			<y>80.0</y>&#13;
+			<final/>&#13;
+		</state>&#13;
+		<transition>&#13;
+			<from>3</from>&#13;
+			<to>3</to>&#13;
+			<read tape="1">a</read>&#13;
+			<write tape="1">a</write>&#13;
+			<move tape="1">R</move>&#13;
+			<read tape="2"/>&#13;
+			<write tape="2">a</write>&#13;
+			<move tape="2">S</move>&#13;
+		</transition>&#13;
+		<transition>&#13;
+			<from>3</from>&#13;
+			<to>7</to>&#13;
+			<read tape="1"/>&#13;
+			<write tape="1"/>&#13;
+			<move tape="1">L</move>&#13;
+			<read tape="2">~</read>&#13;
+			<write tape="2">~</write>&#13;
+			<move tape="2">S</move>&#13;
+		</transition>&#13;
+		<transition>&#13;
+			<from>3</from>&#13;
+			<to>7</to>&#13;
+			<read tape="1">a</read>&#13;
+			<read tape="2">a</read>&#13;
+			<write tape="1">b</write>&#13;
+			<write tape="2">a</write>&#13;
+			<move tape="1">L</move>&#13;
+			<move tape="2">S</move>&#13;
+		</transition>&#13;
+	</automaton>&#13;
+</structure>"#;
+
+    fn labels(description: &Description) -> Vec<(usize, &str, usize)> {
+        description.transitions.iter().map(|t| (t.from, t.label.as_str(), t.to)).collect()
+    }
+
+    #[test]
+    fn single_tape() {
+        let (description, warnings) = parse(SINGLE_TAPE).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(description.tapes, 1);
+        assert_eq!(description.blank, '*');
+        assert_eq!(description.states[0].name, "q0");
+        assert!(description.states[1].is_final);
+        assert_eq!(description.states[0].position, Some(crate::gui_editor::layout::ORIGIN));
+
+        // The wildcard goes over every symbol and the blank
+        assert_eq!(labels(&description), [
+            (0, "a/b", 0),
+            (0, "b/*", 0),
+            (0, "*/1", 1),
+            (1, "1/L", 1),
+            (1, "a/L", 1),
+            (1, "b/L", 1),
+            (1, "*/L", 1),
+        ]);
+    }
+
+    #[test]
+    fn multiple_tapes() {
+        let (description, warnings) = parse(TWO_TAPES).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(description.tapes, 2);
+        let names: Vec<&str> = description.states.iter().map(|s| s.name.as_str()).collect();
+        // Only the tape that writes b and moves left needs a state in between
+        assert_eq!(names, ["copy", "end", "end~0"]);
+        assert_eq!(labels(&description), [
+            (0, "a*/Ra", 0),
+            (0, "*a/La", 1),
+            (0, "*b/Lb", 1),
+            (0, "**/L*", 1),
+            (2, "ba/La", 1),
+            (0, "aa/ba", 2),
+        ]);
+
+        let printed = print(&description).unwrap();
+        assert!(printed.contains("<read tape=\"2\"/>"), "{}", printed);
+        let (again, _) = parse(&printed).unwrap();
+        assert_eq!(again.states.len(), 3);
+        assert_eq!(print(&again).unwrap(), printed);
+    }
+
+    #[test]
+    fn unsupported_constructs_are_reported() {
+        let source = SINGLE_TAPE
+            .replace("<read>a</read>", "<read>ab</read>")
+            .replace("<write>1</write>", "<write>R</write>")
+            .replace("\t</automaton>", "\t\t<note><text>scratch</text></note>\n\t\t<block id=\"9\" name=\"sub\"/>\n\t</automaton>");
+        let (description, warnings) = parse(&source).unwrap();
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert!(warnings.iter().any(|w| w.contains("building blocks")));
+        assert!(warnings.iter().any(|w| w.contains("notes")));
+        assert!(warnings.iter().any(|w| w.starts_with("Line 16") && w.contains("'ab'")));
+        assert!(warnings.iter().any(|w| w.contains("Symbol R is reserved")));
+        let labels: Vec<&str> = description.transitions.iter().map(|t| t.label.as_str()).collect();
+        assert_eq!(labels, ["b/*", "a/L", "b/L", "*/L"]);
+
+        let nondeterministic = SINGLE_TAPE.replace("<read>b</read>", "<read>a</read>");
+        let (description, warnings) = parse(&nondeterministic).unwrap();
+        assert_eq!(warnings, ["Line 23: nondeterministic transition on a was dropped"]);
+        assert_eq!(description.transitions[0].label, "a/b");
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("<structure><type>turing</type>").is_err());
+        assert!(parse("<structure><type>fa</type><automaton/></structure>").is_err());
+        let unknown_state = TWO_TAPES.replace("<to>7</to>", "<to>8</to>");
+        assert!(parse(&unknown_state).err().and_then(|e| e.line).is_some());
+        let missing_tape = TWO_TAPES.replace("<move tape=\"2\">S</move>", "");
+        assert!(parse(&missing_tape).err().is_some_and(|e| e.message.contains("tape 2")));
+    }
+}
@@ -0,0 +1,335 @@
+pub mod text;
+pub mod jflap;
+pub mod yaml;
+pub mod morphett;
+pub mod busy_beaver;
+pub mod dot;
+pub mod svg;
+pub mod tikz;
+
+use egui::Pos2;
+use std::collections::HashMap;
+
+use super::node::Node;
+use super::arrow::Arrow;
+use super::ui::{FG, FG_1};
+use super::layout;
+use crate::turing;
+
+/// Error found while reading a file, `line` starts from 1
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn at(line: usize, message: String) -> Self {
+        ParseError { line: Some(line), message }
+    }
+    pub fn general(message: &str) -> Self {
+        ParseError { line: None, message: message.to_owned() }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "Line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Imported machine with the warnings about what could not be represented
+pub type ImportResult = Result<(Description, Vec<String>), ParseError>;
+
+/// A transition written as read, write, move like most simulators expect
+pub struct Quintuple {
+    pub from: usize,
+    pub to: usize,
+    pub read: Vec<char>,
+    pub write: Vec<char>,
+    pub moves: Vec<Move>,
+}
+
+pub struct StateDescription {
+    pub name: String,
+    pub position: Option<Pos2>,
+    pub is_final: bool,
+}
+
+pub struct TransitionDescription {
+    pub from: usize,
+    pub to: usize,
+    pub label: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Move {
+    Left,
+    Right,
+    Stay,
+}
+
+/// Nodes are 100 by 100, formats storing centers need this
+pub const NODE_HALF_SIZE: f32 = 50.0;
+
+/// Symbols with a special meaning inside labels, they can't be used on tapes
+pub const RESERVED_SYMBOLS: [char; 3] = ['L', 'R', '/'];
+
+pub fn make_label(read: &[char], action: &[char]) -> String {
+    let mut label: String = read.iter().collect();
+    label.push('/');
+    label.extend(action.iter());
+    label
+}
+
+/// Splits a label in the symbols read and the actions, one per tape, for formats that
+/// only have single character symbols
+pub fn split_label(label: &str, tapes: u8) -> Result<(Vec<char>, Vec<char>), String> {
+    let (read, action) = turing::label::split(label, tapes as usize)?;
+    let single = |symbols: Vec<String>| symbols.into_iter().map(|s| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(format!("Label '{}' uses the symbol '{}', the format only has single character symbols", label, s)),
+        }
+    }).collect::<Result<Vec<char>, String>>();
+    Ok((single(read)?, single(action)?))
+}
+
+/// Every way of picking one symbol per position
+pub fn combinations(options: &[Vec<char>]) -> Vec<Vec<char>> {
+    let mut result: Vec<Vec<char>> = vec![vec![]];
+    for choices in options {
+        result = result.into_iter().flat_map(|prefix| {
+            choices.iter().map(move |c| {
+                let mut next = prefix.clone();
+                next.push(*c);
+                next
+            })
+        }).collect();
+    }
+    result
+}
+
+/// Nodes in the order `Description::from_diagram` lists them, node 0 first since the
+/// editor starts every computation there
+pub fn state_order(nodes: &[Option<Node>]) -> Vec<&Node> {
+    let mut order: Vec<&Node> = nodes.iter().flatten().collect();
+    if let Some(start) = order.iter().position(|node| node.id == 0) {
+        let node = order.remove(start);
+        order.insert(0, node);
+    }
+    order
+}
+
+/// Index in a description built by `Description::from_diagram` for every node id
+pub fn state_indices(nodes: &[Option<Node>]) -> HashMap<usize, usize> {
+    state_order(nodes).into_iter().enumerate().map(|(i, node)| (node.id, i)).collect()
+}
+
+/// Format independent view of a machine, the start state is always `states[0]`.
+pub struct Description {
+    pub tapes: u8,
+    pub blank: char,
+    pub input: Option<String>,
+    pub states: Vec<StateDescription>,
+    pub transitions: Vec<TransitionDescription>,
+}
+
+impl Description {
+    pub fn new(tapes: u8, blank: char) -> Self {
+        Description {
+            tapes,
+            blank,
+            input: None,
+            states: vec![],
+            transitions: vec![],
+        }
+    }
+
+    pub fn from_diagram(nodes: &[Option<Node>], arrows: &[Option<Arrow>], tapes: u8, blank: char) -> Self {
+        let mut description = Description::new(tapes, blank);
+        let index_of = state_indices(nodes);
+        for node in state_order(nodes) {
+            description.states.push(StateDescription {
+                name: node.label.clone(),
+                position: Some(node.top_left),
+                is_final: node.is_final,
+            });
+        }
+        for arrow in arrows.iter().flatten() {
+            let (Some(from), Some(to)) = (
+                index_of.get(&arrow.id_from_node),
+                arrow.id_to_node.and_then(|id| index_of.get(&id))
+            ) else { continue };
+            for label in &arrow.labels {
+                description.transitions.push(TransitionDescription { from: *from, to: *to, label: label.clone() });
+            }
+        }
+        description
+    }
+
+    /// Builds nodes and arrows, ids follow the order of `states`.
+    /// States without a position are laid out from the start state.
+    pub fn into_diagram(self) -> (Vec<Option<Node>>, Vec<Option<Arrow>>) {
+        let positions = self.positions();
+        let nodes = self.states.into_iter().zip(positions).enumerate().map(|(id, (state, position))| {
+            let mut node = Node::new(id, state.name, position, if state.is_final { FG_1 } else { FG }, true);
+            node.is_final = state.is_final;
+            Some(node)
+        }).collect();
+
+        let mut arrows: Vec<Option<Arrow>> = vec![];
+        let mut arrow_of = HashMap::<(usize, usize), usize>::new();
+        for t in self.transitions {
+            let id = *arrow_of.entry((t.from, t.to)).or_insert_with(|| {
+                arrows.push(Some(Arrow::new(arrows.len(), Pos2::ZERO, Pos2::ZERO, t.from, Some(t.to))));
+                arrows.len() - 1
+            });
+            if let Some(arrow) = &mut arrows[id] { arrow.add_label(t.label); }
+        }
+        (nodes, arrows)
+    }
+
+    /// Top left corner of every state, laying out the ones without a position
+    pub fn positions(&self) -> Vec<Pos2> {
+        let edges: Vec<(usize, usize)> = self.transitions.iter().map(|t| (t.from, t.to)).collect();
+        let generated = if self.states.iter().any(|s| s.position.is_none()) {
+            layout::layered(self.states.len(), &edges, 0)
+        } else {
+            vec![]
+        };
+        self.states.iter().enumerate().map(|(i, state)| state.position.unwrap_or_else(|| generated[i])).collect()
+    }
+
+    pub fn add_state(&mut self, name: String, position: Option<Pos2>, is_final: bool) -> usize {
+        self.states.push(StateDescription { name, position, is_final });
+        self.states.len() - 1
+    }
+
+    /// Adds a transition that writes and then moves like in most simulators.
+    /// Machines in the editor either write or move on each tape at every step, so when a tape
+    /// needs both the moves happen in an intermediate state, shared by equal continuations.
+    pub fn add_quintuple(
+        &mut self,
+        intermediate: &mut HashMap<(Vec<char>, Vec<Move>, usize), usize>,
+        from: usize, read: &[char], write: &[char], moves: &[Move], to: usize
+    ) {
+        let move_actions: Vec<char> = write.iter().zip(moves).map(|(w, m)| match m {
+            Move::Left => 'L',
+            Move::Right => 'R',
+            Move::Stay => *w,
+        }).collect();
+
+        let needs_both = read.iter().zip(write).zip(moves).any(|((r, w), m)| r != w && *m != Move::Stay);
+        if !needs_both {
+            self.transitions.push(TransitionDescription { from, to, label: make_label(read, &move_actions) });
+            return;
+        }
+
+        let key = (write.to_vec(), moves.to_vec(), to);
+        let via = match intermediate.get(&key) {
+            Some(via) => *via,
+            None => {
+                let position = match (self.states[from].position, self.states[to].position) {
+                    (Some(a), Some(b)) if from != to => Some(a.lerp(b, 0.5) + egui::vec2(0.0, 60.0)),
+                    (Some(a), _) => Some(a + egui::vec2(0.0, 170.0)),
+                    _ => None,
+                };
+                let name = format!("{}~{}", self.states[to].name, intermediate.len());
+                let via = self.add_state(name, position, false);
+                self.transitions.push(TransitionDescription { from: via, to, label: make_label(write, &move_actions) });
+                intermediate.insert(key, via);
+                via
+            }
+        };
+        self.transitions.push(TransitionDescription { from, to: via, label: make_label(read, write) });
+    }
+
+    /// Translates known positions so that the diagram starts where new nodes are placed
+    pub fn move_to_origin(&mut self) {
+        let positions = self.states.iter().filter_map(|s| s.position);
+        let Some(min) = positions.reduce(|a, b| a.min(b)) else { return };
+        let delta = layout::ORIGIN - min;
+        for state in &mut self.states {
+            if let Some(p) = &mut state.position { *p += delta; }
+        }
+    }
+
+    /// Every transition as a quintuple, tapes that move rewrite what they read and
+    /// tapes that write stay where they are
+    pub fn quintuples(&self) -> Result<Vec<Quintuple>, String> {
+        self.transitions.iter().map(|t| {
+            let (read, actions) = split_label(&t.label, self.tapes)?;
+            let mut write = vec![];
+            let mut moves = vec![];
+            for (r, a) in read.iter().zip(&actions) {
+                let (w, m) = match a {
+                    'L' => (*r, Move::Left),
+                    'R' => (*r, Move::Right),
+                    a => (*a, Move::Stay),
+                };
+                write.push(w);
+                moves.push(m);
+            }
+            Ok(Quintuple { from: t.from, to: t.to, read, write, moves })
+        }).collect()
+    }
+
+    /// Like `quintuples`, but a write followed by a state whose only job is to move over
+    /// the written symbols becomes one quintuple, undoing what `add_quintuple` splits.
+    /// Also tells which states are still reachable by some transition or are the start.
+    pub fn merged_quintuples(&self) -> Result<(Vec<Quintuple>, Vec<bool>), String> {
+        let quintuples = self.quintuples()?;
+        let mut outgoing = vec![vec![]; self.states.len()];
+        for (i, q) in quintuples.iter().enumerate() { outgoing[q.from].push(i); }
+
+        let is_move_step = |state: usize| {
+            state != 0 && !self.states[state].is_final && outgoing[state].len() == 1 && {
+                let q = &quintuples[outgoing[state][0]];
+                q.read == q.write && q.to != state
+            }
+        };
+
+        let mut needed = vec![false; self.states.len()];
+        if !needed.is_empty() { needed[0] = true; }
+        let mut merged = vec![];
+        for q in &quintuples {
+            let only_writes = q.moves.iter().all(|m| *m == Move::Stay);
+            if only_writes && is_move_step(q.to) {
+                let step = &quintuples[outgoing[q.to][0]];
+                if step.read == q.write {
+                    merged.push(Quintuple { from: q.from, to: step.to, read: q.read.clone(), write: q.write.clone(), moves: step.moves.clone() });
+                    needed[step.to] = true;
+                    continue;
+                }
+            }
+            needed[q.to] = true;
+            merged.push(Quintuple { from: q.from, to: q.to, read: q.read.clone(), write: q.write.clone(), moves: q.moves.clone() });
+        }
+        merged.retain(|q| needed[q.from] || !is_move_step(q.from));
+        for (state, n) in needed.iter_mut().enumerate() {
+            if !is_move_step(state) { *n = true; }
+        }
+        Ok((merged, needed))
+    }
+
+    /// Names that are unique and never empty, falling back to `q<index>`
+    pub fn unique_names(&self) -> Vec<String> {
+        let mut taken = std::collections::HashSet::<String>::new();
+        self.states.iter().enumerate().map(|(i, state)| {
+            let name = if state.name.trim().is_empty() || taken.contains(&state.name) {
+                let mut fallback = format!("q{}", i);
+                while taken.contains(&fallback) { fallback.push('_'); }
+                fallback
+            } else {
+                state.name.clone()
+            };
+            taken.insert(name.clone());
+            name
+        }).collect()
+    }
+}
@@ -0,0 +1,183 @@
+//! Programs for Anthony Morphett's simulator, one rule per line:
+//!
+//! ```text
+//! ; current-state current-symbol new-symbol direction new-state
+//! 0 * * r 0
+//! 0 _ 1 l halt
+//! ```
+//!
+//! `_` is the blank, `*` matches any symbol / state or leaves it unchanged, directions are
+//! `l`, `r` and `*` (stay). Wildcards are expanded over every symbol the program uses plus
+//! the extra ones given, usually the ones in the current input. States named `halt...`
+//! stop the machine, all of them but `halt-reject` become final nodes.
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::{Description, ImportResult, Move, ParseError, RESERVED_SYMBOLS};
+
+const WILDCARD: &str = "*";
+const BLANK: char = '_';
+
+struct Rule {
+    state: String,
+    read: Option<char>,
+    write: Option<char>,
+    direction: Move,
+    next: Option<String>,
+    line: usize,
+}
+
+fn symbol(token: &str, line: usize) -> Result<Option<char>, ParseError> {
+    if token == WILDCARD { return Ok(None) }
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Some(c)),
+        _ => Err(ParseError::at(line, format!("Symbol '{}' must be a single character", token))),
+    }
+}
+
+fn is_halting(state: &str) -> bool {
+    state.starts_with("halt")
+}
+
+pub fn parse(source: &str, extra_symbols: &[char]) -> ImportResult {
+    let mut warnings = vec![];
+    let mut rules = vec![];
+    let mut breakpoints = false;
+
+    for (i, line) in source.lines().enumerate() {
+        let n = i + 1;
+        let code = line.split(';').next().unwrap_or("");
+        let tokens: Vec<&str> = code.split_whitespace().collect();
+        if tokens.is_empty() { continue }
+        if tokens.len() != 5 && !(tokens.len() == 6 && tokens[5] == "!") {
+            return Err(ParseError::at(n, format!("Expected 'state symbol new-symbol direction new-state', found {} fields", tokens.len())))
+        }
+        breakpoints |= tokens.len() == 6;
+        let direction = match tokens[3].to_lowercase().as_str() {
+            "l" => Move::Left,
+            "r" => Move::Right,
+            WILDCARD => Move::Stay,
+            other => return Err(ParseError::at(n, format!("Unknown direction '{}', expected l, r or *", other))),
+        };
+        rules.push(Rule {
+            state: tokens[0].to_owned(),
+            read: symbol(tokens[1], n)?,
+            write: symbol(tokens[2], n)?,
+            direction,
+            next: (tokens[4] != WILDCARD).then(|| tokens[4].to_owned()),
+            line: n,
+        });
+    }
+    if rules.is_empty() { return Err(ParseError::general("The program has no rules")) }
+    if breakpoints { warnings.push("Breakpoints ('!') are not supported and were ignored".to_owned()); }
+
+    // Alphabet
+    let mut alphabet = BTreeSet::<char>::new();
+    alphabet.insert(BLANK);
+    alphabet.extend(extra_symbols.iter().copied());
+    for rule in &rules {
+        alphabet.extend(rule.read.iter().chain(rule.write.iter()).copied());
+    }
+    for c in alphabet.iter().filter(|c| RESERVED_SYMBOLS.contains(c)) {
+        warnings.push(format!("Symbol {} is reserved in labels, rules using it were dropped", c));
+    }
+    alphabet.retain(|c| !RESERVED_SYMBOLS.contains(c));
+
+    // States, "0" starts when present like in the simulator
+    let mut names: Vec<String> = vec![];
+    for rule in &rules {
+        for name in std::iter::once(&rule.state).chain(rule.next.iter()) {
+            if name != WILDCARD && !names.contains(name) { names.push(name.clone()); }
+        }
+    }
+    if names.is_empty() { return Err(ParseError::general("The program has no named states, only '*'")) }
+    match names.iter().position(|n| n == "0") {
+        Some(start) => { let zero = names.remove(start); names.insert(0, zero); }
+        None => warnings.push(format!("There is no state 0, {} will be the start state", names[0])),
+    }
+
+    let mut description = Description::new(1, BLANK);
+    let mut index_of = HashMap::<String, usize>::new();
+    for name in names {
+        let is_final = is_halting(&name) && name != "halt-reject";
+        index_of.insert(name.clone(), description.add_state(name, None, is_final));
+    }
+
+    // Most specific rule first: state and symbol, state only, symbol only, neither
+    let mut intermediate = HashMap::new();
+    let states: Vec<String> = description.states.iter().map(|s| s.name.clone()).collect();
+    for state in states.iter().filter(|s| !is_halting(s)) {
+        for symbol in &alphabet {
+            let matches = |state_wild: bool, symbol_wild: bool| rules.iter().find(|r| {
+                (if state_wild { r.state == WILDCARD } else { r.state == *state })
+                    && (if symbol_wild { r.read.is_none() } else { r.read == Some(*symbol) })
+            });
+            let Some(rule) = matches(false, false)
+                .or_else(|| matches(false, true))
+                .or_else(|| matches(true, false))
+                .or_else(|| matches(true, true))
+            else { continue };
+
+            let write = rule.write.unwrap_or(*symbol);
+            if RESERVED_SYMBOLS.contains(&write) { continue }
+            let from = index_of[state];
+            let to = rule.next.as_ref().map(|n| index_of[n]).unwrap_or(from);
+            description.add_quintuple(&mut intermediate, from, &[*symbol], &[write], &[rule.direction], to);
+        }
+    }
+    for rule in rules.iter().filter(|r| is_halting(&r.state)) {
+        warnings.push(format!("Line {}: rules for the halting state {} are never used", rule.line, rule.state));
+    }
+    if description.states.len() > 256 {
+        return Err(ParseError::general(&format!("{} states are needed, at most 256 are supported", description.states.len())))
+    }
+
+    Ok((description, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::text;
+
+    const SOURCE: &str = "\
+; go right, then write 1 on the first blank
+0 * * r 0
+0 _ 1 l halt
+";
+
+    #[test]
+    fn round_trip() {
+        let (description, warnings) = parse(SOURCE, &[]).unwrap();
+        assert!(warnings.is_empty());
+        let names: Vec<&str> = description.states.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["0", "halt", "halt~0"]);
+        assert!(description.states[1].is_final);
+        let labels: Vec<&str> = description.transitions.iter().map(|t| t.label.as_str()).collect();
+        assert_eq!(labels, ["1/R", "1/L", "_/1"]);
+
+        // Imported programs can be edited as text
+        let printed = text::print(&description);
+        assert_eq!(text::print(&text::parse(&printed).unwrap()), printed);
+    }
+
+    #[test]
+    fn wildcards_cover_the_alphabet_and_halt_reject_is_not_final() {
+        let source = "0 * * r 0\n0 _ _ * halt-reject\n0 a * l halt-accept\n";
+        let (description, _) = parse(source, &['x']).unwrap();
+        let states: Vec<(&str, bool)> = description.states.iter().map(|s| (s.name.as_str(), s.is_final)).collect();
+        assert_eq!(states, [("0", false), ("halt-reject", false), ("halt-accept", true)]);
+        // x is only known from the extra symbols, a and _ have rules of their own
+        let labels: Vec<(usize, &str, usize)> = description.transitions.iter().map(|t| (t.from, t.label.as_str(), t.to)).collect();
+        assert_eq!(labels, [(0, "_/_", 1), (0, "a/L", 2), (0, "x/R", 0)]);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("* * * r *\n", &[]).is_err());
+        assert_eq!(parse("0 _ 1 l\n", &[]).err().map(|e| e.line), Some(Some(1)));
+        assert!(parse("0 _ 1 up halt\n", &[]).is_err());
+        assert!(parse("; only a comment\n", &[]).is_err());
+    }
+}
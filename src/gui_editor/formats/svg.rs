@@ -0,0 +1,137 @@
+//! SVG picture of the canvas, drawn with the same shapes and colors the editor paints.
+
+use egui::{Color32, Pos2, Rect};
+
+use super::super::arrow::Arrow;
+use super::super::node::{Node, BACKGROUND_COLOR};
+use super::super::TEXT_COLOR;
+
+const MARGIN: f32 = 20.0;
+const CURVE_SAMPLES: usize = 32;
+const FONT: &str = "font-family=\"monospace\" font-size=\"15\" text-anchor=\"middle\" dominant-baseline=\"central\"";
+
+fn color(c: Color32) -> String {
+    if c.a() == 255 {
+        format!("#{:02X}{:02X}{:02X}", c.r(), c.g(), c.b())
+    } else {
+        format!("rgba({},{},{},{:.3})", c.r(), c.g(), c.b(), c.a() as f32 / 255.0)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn text(out: &mut String, position: Pos2, content: &str, fill: Color32) {
+    out.push_str(&format!(
+        "  <text x=\"{:.1}\" y=\"{:.1}\" {} fill=\"{}\">{}</text>\n",
+        position.x, position.y, FONT, color(fill), escape(content)
+    ));
+}
+
+fn rect(out: &mut String, rect: Rect, fill: &str, stroke: Option<(f32, Color32)>) {
+    let stroke = stroke.map(|(width, c)| format!(" stroke=\"{}\" stroke-width=\"{}\"", color(c), width)).unwrap_or_default();
+    out.push_str(&format!(
+        "  <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"10\" fill=\"{}\"{}/>\n",
+        rect.min.x, rect.min.y, rect.width(), rect.height(), fill, stroke
+    ));
+}
+
+/// Arrows take their ends from the nodes like when the canvas is painted
+pub fn print(nodes: &[Option<Node>], arrows: &[Option<Arrow>]) -> String {
+    let node_of = |id: usize| nodes.get(id).and_then(|n| n.as_ref());
+    let arrows: Vec<Arrow> = arrows.iter().flatten().filter_map(|arrow| {
+        let (from, to) = (node_of(arrow.id_from_node)?, node_of(arrow.id_to_node?)?);
+        let mut placed = Arrow::new(arrow.id, from.get_output_edge(), to.get_input_edge(), from.id, Some(to.id));
+        placed.labels = arrow.labels.clone();
+        Some(placed)
+    }).collect();
+
+    let mut bounds = Rect::NOTHING;
+    for node in nodes.iter().flatten() { bounds = bounds.union(node.rect()); }
+    for arrow in &arrows {
+        for i in 0..=CURVE_SAMPLES { bounds.extend_with(arrow.point_at(i as f32 / CURVE_SAMPLES as f32)); }
+        for p in arrow.tip_points() { bounds.extend_with(p); }
+        for (i, label) in arrow.labels.iter().enumerate() {
+            // Monospace glyphs are about 9 pixels wide at this size
+            let half = egui::vec2(label.chars().count() as f32 * 4.5, 10.0);
+            bounds = bounds.union(Rect::from_center_size(arrow.label_position(i), half * 2.0));
+        }
+    }
+    if !bounds.is_positive() { bounds = Rect::from_min_size(Pos2::ZERO, egui::vec2(100.0, 100.0)); }
+    let bounds = bounds.expand(MARGIN);
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.1} {:.1} {:.1} {:.1}\" width=\"{:.0}\" height=\"{:.0}\">\n",
+        bounds.min.x, bounds.min.y, bounds.width(), bounds.height(), bounds.width(), bounds.height()
+    );
+    rect(&mut out, bounds, &color(egui::Visuals::dark().panel_fill), None);
+
+    for node in nodes.iter().flatten() {
+        // The editor strokes inside the border
+        rect(&mut out, node.rect(), &color(BACKGROUND_COLOR), None);
+        rect(&mut out, node.rect().shrink(1.25), "none", Some((2.5, node.foreground_color)));
+        text(&mut out, node.rect().center(), &node.label, TEXT_COLOR);
+        if node.separate_header {
+            rect(&mut out, node.header_rect(), &color(node.foreground_color), None);
+            text(&mut out, node.header_text_position(), &node.id.to_string(), TEXT_COLOR);
+        }
+    }
+
+    for arrow in &arrows {
+        let stroke = arrow.stroke();
+        let (c1, c2) = arrow.get_control_offset();
+        let attributes = format!("fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"", color(stroke.color), stroke.width);
+        out.push_str(&format!(
+            "  <path d=\"M {:.1} {:.1} C {:.1} {:.1}, {:.1} {:.1}, {:.1} {:.1}\" {}/>\n",
+            arrow.start.x, arrow.start.y, c1.x, c1.y, c2.x, c2.y, arrow.end.x, arrow.end.y, attributes
+        ));
+        let tip: Vec<String> = arrow.tip_points().iter().map(|p| format!("{:.1},{:.1}", p.x, p.y)).collect();
+        out.push_str(&format!("  <polyline points=\"{}\" {}/>\n", tip.join(" "), attributes));
+        for (i, label) in arrow.labels.iter().enumerate() {
+            text(&mut out, arrow.label_position(i), label, TEXT_COLOR);
+        }
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes() -> Vec<Option<Node>> {
+        vec![
+            Some(Node::new(0, "a".to_owned(), Pos2::ZERO, Color32::RED, true)),
+            Some(Node::new(1, "b".to_owned(), Pos2::new(300.0, 0.0), Color32::RED, false)),
+        ]
+    }
+
+    #[test]
+    fn two_nodes() {
+        let arrows = vec![Some(Arrow::new(0, Pos2::ZERO, Pos2::ZERO, 0, Some(1)))];
+        let printed = print(&nodes(), &arrows);
+        assert!(printed.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-20.0 -20.0 440.0 140.0\" width=\"440\" height=\"140\">\n"), "{}", printed);
+        // Body and header of the first node, the second has no header
+        assert!(printed.contains("<rect x=\"0.0\" y=\"0.0\" width=\"100.0\" height=\"100.0\" rx=\"10\" fill=\"#333333\"/>"), "{}", printed);
+        assert!(printed.contains("<rect x=\"0.0\" y=\"0.0\" width=\"100.0\" height=\"25.0\" rx=\"10\" fill=\"#FF0000\"/>"), "{}", printed);
+        assert_eq!(printed.matches("height=\"25.0\"").count(), 1);
+        // From the right edge of a to the left edge of b, control points 80 pixels in
+        assert!(printed.contains("<path d=\"M 100.0 50.0 C 180.0 50.0, 220.0 50.0, 300.0 50.0\""), "{}", printed);
+        assert!(printed.contains("<polyline points=\"290.0,60.0 300.0,50.0 290.0,40.0\""), "{}", printed);
+        assert!(printed.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn loops_and_labels() {
+        let mut arrow = Arrow::new(0, Pos2::ZERO, Pos2::ZERO, 1, Some(1));
+        arrow.labels = vec!["a<b/R".to_owned()];
+        let printed = print(&nodes(), &[Some(arrow), None]);
+        assert!(printed.contains("<path d=\"M 400.0 50.0 C 480.0 170.0, 220.0 170.0, 300.0 50.0\""), "{}", printed);
+        assert!(printed.contains(">a&lt;b/R</text>"), "{}", printed);
+        // Arrows to nodes that are gone are left out
+        let dangling = Arrow::new(1, Pos2::ZERO, Pos2::ZERO, 0, Some(5));
+        assert!(!print(&nodes(), &[Some(dangling)]).contains("<path"));
+    }
+}
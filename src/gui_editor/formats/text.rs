@@ -0,0 +1,310 @@
+//! Plain text machine definitions.
+//!
+//! ```text
+//! # Lines starting with '#' are comments
+//! tapes: 1
+//! blank: *
+//! states: q0 @ (50, 150), "go right", done, fail
+//! start: q0
+//! accept: done
+//! reject: fail
+//! q0 -> "go right" : a/R
+//! "go right" -> done : x1/R
+//! ```
+//!
+//! Names are either made of letters, digits and '_' or written between double quotes.
+//! Positions are optional, states without one are laid out automatically.
+//! A blank that is whitespace or '"' is written between double quotes too.
+//! The editor has no reject states, so `reject:` is only read to check that the states it
+//! lists neither accept nor have outgoing transitions, and it is never written back.
+
+use egui::Pos2;
+use std::collections::HashMap;
+
+use crate::turing::label;
+
+use super::{Description, ParseError, StateDescription, TransitionDescription};
+
+const KEYS: [&str; 6] = ["tapes", "blank", "states", "start", "accept", "reject"];
+
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(s: &'a str) -> Self {
+        Cursor { chars: s.chars().peekable() }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&expected).is_some()
+    }
+
+    fn is_done(&mut self) -> bool {
+        self.skip_whitespace();
+        self.chars.peek().is_none()
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        let mut name = String::new();
+        if self.chars.next_if_eq(&'"').is_some() {
+            loop {
+                match self.chars.next() {
+                    None => return Err("Unterminated quoted name".to_owned()),
+                    Some('"') => break,
+                    Some('\\') => name.push(self.chars.next().ok_or("Unterminated quoted name")?),
+                    Some(c) => name.push(c),
+                }
+            }
+            return Ok(name)
+        }
+        while let Some(c) = self.chars.next_if(|c| is_name_char(*c)) {
+            name.push(c);
+        }
+        if name.is_empty() { Err("Expected a state name".to_owned()) } else { Ok(name) }
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_whitespace();
+        let mut number = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')) {
+            number.push(c);
+        }
+        number.parse().map_err(|_| format!("Could not parse number '{}'", number))
+    }
+
+    fn rest(&mut self) -> String {
+        self.chars.by_ref().collect::<String>().trim().to_owned()
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn write_name(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(is_name_char) { name.to_owned() } else { quote(name) }
+}
+
+/// Values are trimmed when read, so blanks they would lose are quoted
+fn write_blank(blank: char) -> String {
+    if blank.is_whitespace() || blank == '"' { quote(&blank.to_string()) } else { blank.to_string() }
+}
+
+pub fn parse(source: &str) -> Result<Description, ParseError> {
+    let mut tapes: u8 = 1;
+    let mut blank = crate::turing::alphabet::DEFAULT_BLANK;
+    let mut states: Vec<StateDescription> = vec![];
+    let mut index_of = HashMap::<String, usize>::new();
+    let mut start: Option<usize> = None;
+    let mut rejects: Vec<(usize, usize)> = vec![];
+    let mut transitions: Vec<(usize, TransitionDescription)> = vec![];
+
+    for (i, line) in source.lines().enumerate() {
+        let n = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue }
+        let at = |e: String| ParseError::at(n, e);
+
+        let Some((key, value)) = line.split_once(':').filter(|(key, _)| KEYS.contains(&key.trim())) else {
+            let mut cursor = Cursor::new(line);
+            let from = cursor.name().map_err(at)?;
+            if !(cursor.eat('-') && cursor.eat('>')) { return Err(at("Expected '->' or one of tapes, blank, states, start, accept, reject".to_owned())) }
+            let to = cursor.name().map_err(at)?;
+            if !cursor.eat(':') { return Err(at("Expected ':' before the label".to_owned())) }
+            let label = cursor.rest();
+
+            let from = *index_of.get(&from).ok_or_else(|| at(format!("Unknown state {}", from)))?;
+            let to = *index_of.get(&to).ok_or_else(|| at(format!("Unknown state {}", to)))?;
+            transitions.push((n, TransitionDescription { from, to, label }));
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "tapes" => {
+                tapes = value.parse().map_err(|_| at(format!("Could not parse tape count '{}'", value)))?;
+                if !(1..=3).contains(&tapes) { return Err(at("Tape count must be 1, 2 or 3".to_owned())) }
+            }
+            "blank" => {
+                let value = if value.starts_with('"') {
+                    let mut cursor = Cursor::new(value);
+                    let quoted = cursor.name().map_err(at)?;
+                    if !cursor.is_done() { return Err(at("Unexpected text after the quoted blank".to_owned())) }
+                    quoted
+                } else { value.to_owned() };
+                let mut chars = value.chars();
+                blank = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(at(format!("Blank must be a single symbol, found '{}'", value))),
+                };
+            }
+            "states" => {
+                let mut cursor = Cursor::new(value);
+                while !cursor.is_done() {
+                    let name = cursor.name().map_err(at)?;
+                    let position = if cursor.eat('@') {
+                        if !cursor.eat('(') { return Err(at("Expected '(' after '@'".to_owned())) }
+                        let x = cursor.number().map_err(at)?;
+                        if !cursor.eat(',') { return Err(at("Expected ',' between coordinates".to_owned())) }
+                        let y = cursor.number().map_err(at)?;
+                        if !cursor.eat(')') { return Err(at("Expected ')' after coordinates".to_owned())) }
+                        Some(Pos2::new(x, y))
+                    } else { None };
+                    if index_of.contains_key(&name) { return Err(at(format!("State {} is declared twice", name))) }
+                    index_of.insert(name.clone(), states.len());
+                    states.push(StateDescription { name, position, is_final: false });
+                    if !cursor.is_done() && !cursor.eat(',') { return Err(at("Expected ',' between states".to_owned())) }
+                }
+            }
+            key @ ("start" | "accept" | "reject") => {
+                let mut cursor = Cursor::new(value);
+                while !cursor.is_done() {
+                    let name = cursor.name().map_err(at)?;
+                    let q = *index_of.get(&name).ok_or_else(|| at(format!("Unknown state {}", name)))?;
+                    match key {
+                        "start" => {
+                            if start.is_some() { return Err(at("Only one start state is allowed".to_owned())) }
+                            start = Some(q);
+                        }
+                        "accept" => states[q].is_final = true,
+                        _ => rejects.push((n, q)),
+                    }
+                    if !cursor.is_done() && !cursor.eat(',') { return Err(at("Expected ',' between states".to_owned())) }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if states.is_empty() { return Err(ParseError::general("No states declared")) }
+    let start = start.ok_or(ParseError::general("No start state declared"))?;
+    for (n, t) in &transitions {
+        label::split(&t.label, tapes as usize).map_err(|e| ParseError::at(*n, e))?;
+    }
+    for (n, q) in &rejects {
+        if states[*q].is_final { return Err(ParseError::at(*n, format!("{} can't both accept and reject", states[*q].name))) }
+        if transitions.iter().any(|(_, t)| t.from == *q) {
+            return Err(ParseError::at(*n, format!("Reject state {} has outgoing transitions", states[*q].name)))
+        }
+    }
+
+    // The computation always starts from the first state
+    let order: Vec<usize> = std::iter::once(start).chain((0..states.len()).filter(|q| *q != start)).collect();
+    let mut new_index = vec![0; states.len()];
+    for (new, old) in order.iter().enumerate() { new_index[*old] = new; }
+    let mut slots: Vec<Option<StateDescription>> = states.into_iter().map(Some).collect();
+
+    let mut description = Description::new(tapes, blank);
+    description.states = order.iter().map(|old| slots[*old].take().unwrap()).collect();
+    description.transitions = transitions.into_iter().map(|(_, t)| TransitionDescription {
+        from: new_index[t.from],
+        to: new_index[t.to],
+        label: t.label,
+    }).collect();
+    Ok(description)
+}
+
+pub fn print(description: &Description) -> String {
+    let names: Vec<String> = description.unique_names().iter().map(|n| write_name(n)).collect();
+    let mut out = String::new();
+
+    out.push_str(&format!("tapes: {}\n", description.tapes));
+    out.push_str(&format!("blank: {}\n", write_blank(description.blank)));
+    let states: Vec<String> = description.states.iter().zip(&names).map(|(state, name)| {
+        match state.position {
+            Some(p) => format!("{} @ ({}, {})", name, p.x, p.y),
+            None => name.clone(),
+        }
+    }).collect();
+    out.push_str(&format!("states: {}\n", states.join(", ")));
+    if let Some(start) = names.first() {
+        out.push_str(&format!("start: {}\n", start));
+    }
+
+    let accept: Vec<&str> = (0..names.len())
+        .filter(|q| description.states[*q].is_final)
+        .map(|q| names[q].as_str())
+        .collect();
+    if !accept.is_empty() { out.push_str(&format!("accept: {}\n", accept.join(", "))); }
+
+    out.push('\n');
+    for t in &description.transitions {
+        out.push_str(&format!("{} -> {} : {}\n", names[t.from], names[t.to], t.label));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+# Two tapes, the blank is a space
+tapes: 2
+blank: \" \"
+states: start @ (50, 150), \"go right\" @ (270, 150), done @ (490, 150), \"say \\\"no\\\"\" @ (270, 320)
+start: start
+accept: done
+reject: \"say \\\"no\\\"\"
+
+  # Transitions, one per line
+start -> \"go right\" : a,b/R,x
+\"go right\" -> done : ab/LR
+";
+
+    #[test]
+    fn round_trip() {
+        let description = parse(SOURCE).unwrap();
+        assert_eq!(description.tapes, 2);
+        assert_eq!(description.blank, ' ');
+        let names: Vec<&str> = description.states.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["start", "go right", "done", "say \"no\""]);
+        assert_eq!(description.states[0].position, Some(Pos2::new(50.0, 150.0)));
+        assert!(description.states[2].is_final && !description.states[3].is_final);
+
+        let printed = print(&description);
+        assert!(printed.contains("blank: \" \"\n"), "{}", printed);
+        assert!(printed.contains("\"say \\\"no\\\"\" @ (270, 320)"), "{}", printed);
+        assert!(!printed.contains("reject:"), "{}", printed);
+        let again = parse(&printed).unwrap();
+        assert_eq!(print(&again), printed);
+        assert_eq!(again.blank, ' ');
+        assert_eq!(again.transitions.len(), 2);
+        assert_eq!(again.transitions[1].label, description.transitions[1].label);
+    }
+
+    #[test]
+    fn start_comes_first_and_missing_positions_are_laid_out() {
+        let description = parse("states: b, a @ (500, 500), c\nstart: a\na -> b : x/R\nb -> c : x/R\n").unwrap();
+        let names: Vec<&str> = description.states.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(description.transitions[0].from, 0);
+
+        let (nodes, _) = description.into_diagram();
+        let positions: Vec<Pos2> = nodes.iter().flatten().map(|n| n.top_left).collect();
+        assert_eq!(positions[0], Pos2::new(500.0, 500.0));
+        assert!(positions[1] != positions[2], "{:?}", positions);
+    }
+
+    #[test]
+    fn malformed() {
+        let error = parse("# comment\n\nstates: a\nstart: a\na -> b : x/R\n").err().expect("b is not declared");
+        assert_eq!(error.line, Some(5));
+        assert_eq!(parse("states: a\nstart: a\na -> a : x\n").err().and_then(|e| e.line), Some(3));
+        assert_eq!(parse("states: \"a\nstart: a\n").err().and_then(|e| e.line), Some(1));
+        assert_eq!(parse("states: a, b\nstart: a\nreject: a\na -> b : x/R\n").err().and_then(|e| e.line), Some(3));
+        assert_eq!(parse("states: a\nstart: a\naccept: a\nreject: a\n").err().and_then(|e| e.line), Some(4));
+        assert_eq!(parse("states: a\n").err().map(|e| e.line), Some(None));
+        assert_eq!(parse("tapes: 4\n").err().and_then(|e| e.line), Some(1));
+    }
+}
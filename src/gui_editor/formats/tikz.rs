@@ -0,0 +1,156 @@
+//! LaTeX output for lecture notes, a TikZ `automata` picture and a `tabular` transition table.
+//!
+//! Both are fragments meant for `\input`, the document needs `\usepackage{tikz}` and
+//! `\usetikzlibrary{automata, arrows.meta}`. A node center moves by 1cm every 100 pixels.
+
+use std::collections::BTreeMap;
+
+use crate::turing::label;
+
+use super::{Description, NODE_HALF_SIZE};
+
+const PIXELS_PER_CM: f32 = 100.0;
+
+/// Escapes the characters TeX gives a meaning to
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => { out.push('\\'); out.push(c); }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn picture(description: &Description) -> String {
+    let names = description.unique_names();
+    let mut out = String::from("% Needs \\usepackage{tikz} and \\usetikzlibrary{automata, arrows.meta}\n");
+    out.push_str("\\begin{tikzpicture}[>=Stealth, auto, every state/.style={minimum size=1cm}]\n");
+
+    for (i, position) in description.positions().into_iter().enumerate() {
+        let mut options = vec!["state"];
+        if i == 0 { options.push("initial"); }
+        if description.states[i].is_final { options.push("accepting"); }
+        // y grows downwards on the canvas and upwards in TikZ
+        let x = (position.x + NODE_HALF_SIZE) / PIXELS_PER_CM;
+        let y = -(position.y + NODE_HALF_SIZE) / PIXELS_PER_CM;
+        out.push_str(&format!("  \\node[{}] (s{}) at ({:.2}, {:.2}) {{{}}};\n", options.join(", "), i, x, y, escape(&names[i])));
+    }
+
+    let mut edges = BTreeMap::<(usize, usize), Vec<String>>::new();
+    for t in &description.transitions {
+        edges.entry((t.from, t.to)).or_default().push(format!("\\texttt{{{}}}", escape(&t.label)));
+    }
+    if !edges.is_empty() {
+        out.push_str("  \\path[->]\n");
+        for ((from, to), labels) in &edges {
+            let label = format!("node[align=center] {{{}}}", labels.join("\\\\"));
+            if from == to {
+                out.push_str(&format!("    (s{}) edge[loop above] {} ()\n", from, label));
+            } else if edges.contains_key(&(*to, *from)) {
+                out.push_str(&format!("    (s{}) edge[bend left] {} (s{})\n", from, label, to));
+            } else {
+                out.push_str(&format!("    (s{}) edge {} (s{})\n", from, label, to));
+            }
+        }
+        out.push_str("  ;\n");
+    }
+    out.push_str("\\end{tikzpicture}\n");
+    out
+}
+
+/// One row per state and one column per combination of symbols read, each cell has the
+/// action and the next state. The start state is marked with an arrow, final ones with a star.
+pub fn table(description: &Description) -> Result<String, String> {
+    let names = description.unique_names();
+    let mut columns: Vec<String> = vec![];
+    let mut cells = BTreeMap::<(usize, String), Vec<String>>::new();
+    for t in &description.transitions {
+        let (read, action) = label::split(&t.label, description.tapes as usize)?;
+        let read = label::join(&read);
+        if !columns.contains(&read) { columns.push(read.clone()); }
+        let action = label::join(&action);
+        cells.entry((t.from, read)).or_default().push(format!("\\texttt{{{}}}, {}", escape(&action), escape(&names[t.to])));
+    }
+    columns.sort();
+
+    let mut out = format!("\\begin{{tabular}}{{|l|{}}}\n  \\hline\n", "c|".repeat(columns.len()));
+    let header: Vec<String> = columns.iter().map(|c| format!("\\texttt{{{}}}", escape(c))).collect();
+    out.push_str(&format!("  State{} \\\\\n  \\hline\n", header.iter().map(|h| format!(" & {}", h)).collect::<String>()));
+    for (q, state) in description.states.iter().enumerate() {
+        let marker = match (q == 0, state.is_final) {
+            (true, true) => "$\\to$ $*$ ",
+            (true, false) => "$\\to$ ",
+            (false, true) => "$*$ ",
+            (false, false) => "",
+        };
+        let row: String = columns.iter().map(|c| {
+            let cell = cells.get(&(q, c.clone())).map(|entries| entries.join("; ")).unwrap_or_else(|| "--".to_owned());
+            format!(" & {}", cell)
+        }).collect();
+        out.push_str(&format!("  {}{}{} \\\\\n", marker, escape(&names[q]), row));
+    }
+    out.push_str("  \\hline\n\\end{tabular}\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Pos2;
+
+    use super::*;
+    use super::super::TransitionDescription;
+
+    /// A start state with a loop and a final one that goes back to it
+    fn machine() -> Description {
+        let mut description = Description::new(1, ' ');
+        let a = description.add_state("a~1".to_owned(), Some(Pos2::ZERO), false);
+        let b = description.add_state("b".to_owned(), Some(Pos2::new(200.0, 100.0)), true);
+        for (from, label, to) in [(a, "0/R", a), (a, "1/0", b), (b, " /L", a)] {
+            description.transitions.push(TransitionDescription { from, to, label: label.to_owned() });
+        }
+        description
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("a~1"), r"a\textasciitilde{}1");
+        assert_eq!(escape(r"x_1 & {$y$}^\%#"), r"x\_1 \& \{\$y\$\}\textasciicircum{}\textbackslash{}\%\#");
+    }
+
+    #[test]
+    fn picture_of_two_states() {
+        assert_eq!(picture(&machine()), r"% Needs \usepackage{tikz} and \usetikzlibrary{automata, arrows.meta}
+\begin{tikzpicture}[>=Stealth, auto, every state/.style={minimum size=1cm}]
+  \node[state, initial] (s0) at (0.50, -0.50) {a\textasciitilde{}1};
+  \node[state, accepting] (s1) at (2.50, -1.50) {b};
+  \path[->]
+    (s0) edge[loop above] node[align=center] {\texttt{0/R}} ()
+    (s0) edge[bend left] node[align=center] {\texttt{1/0}} (s1)
+    (s1) edge[bend left] node[align=center] {\texttt{ /L}} (s0)
+  ;
+\end{tikzpicture}
+");
+    }
+
+    #[test]
+    fn table_of_two_states() {
+        assert_eq!(table(&machine()).unwrap(), r"\begin{tabular}{|l|c|c|c|}
+  \hline
+  State & \texttt{ } & \texttt{0} & \texttt{1} \\
+  \hline
+  $\to$ a\textasciitilde{}1 & -- & \texttt{R}, a\textasciitilde{}1 & \texttt{0}, b \\
+  $*$ b & \texttt{L}, a\textasciitilde{}1 & -- & -- \\
+  \hline
+\end{tabular}
+");
+
+        let mut description = Description::new(1, ' ');
+        description.add_state("a".to_owned(), None, true);
+        assert!(table(&description).unwrap().contains(r"  $\to$ $*$ a \\"));
+    }
+}
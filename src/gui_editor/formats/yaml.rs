@@ -0,0 +1,307 @@
+//! turingmachine.io YAML programs.
+//!
+//! ```yaml
+//! input: '1011'
+//! blank: ' '
+//! start state: right
+//! table:
+//!   right:
+//!     [1, 0]: R
+//!     ' ': {L: carry}
+//!   carry:
+//!     1: {write: 0, L}
+//!     [0, ' ']: {write: 1, L: done}
+//!   done:
+//! ```
+//!
+//! States without instructions become final nodes. The site only knows single tape machines
+//! that always move, so writes without a move go right and come back through a helper state,
+//! while a write followed by a state that only moves becomes a single instruction.
+
+use std::collections::HashMap;
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::turing::label;
+
+use super::{Description, ImportResult, Move, ParseError, RESERVED_SYMBOLS};
+
+fn scalar(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn symbol(value: &Yaml) -> Result<char, String> {
+    let s = scalar(value).ok_or("Symbols must be scalars")?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("Symbol '{}' must be a single character", s)),
+    }
+}
+
+fn symbols(key: &Yaml) -> Result<Vec<char>, String> {
+    match key {
+        Yaml::Array(items) => items.iter().map(symbol).collect(),
+        other => Ok(vec![symbol(other)?]),
+    }
+}
+
+struct Instruction {
+    write: Option<char>,
+    direction: Move,
+    next: Option<String>,
+}
+
+fn instruction(value: &Yaml) -> Result<Instruction, String> {
+    match value {
+        Yaml::Hash(hash) => {
+            let mut result = Instruction { write: None, direction: Move::Stay, next: None };
+            for (key, value) in hash {
+                match scalar(key).as_deref() {
+                    Some("write") => result.write = Some(symbol(value)?),
+                    Some(d @ ("L" | "R")) => {
+                        if result.direction != Move::Stay { return Err("Only one direction is allowed".to_owned()) }
+                        result.direction = if d == "L" { Move::Left } else { Move::Right };
+                        result.next = match value {
+                            Yaml::Null => None,
+                            other => Some(scalar(other).ok_or("The next state must be a name")?),
+                        };
+                    }
+                    _ => return Err("Instructions only accept write, L and R".to_owned()),
+                }
+            }
+            Ok(result)
+        }
+        other => match scalar(other).as_deref() {
+            Some("L") => Ok(Instruction { write: None, direction: Move::Left, next: None }),
+            Some("R") => Ok(Instruction { write: None, direction: Move::Right, next: None }),
+            _ => Err("An instruction is L, R or a mapping like {write: 1, R: next}".to_owned()),
+        }
+    }
+}
+
+pub fn parse(source: &str) -> ImportResult {
+    let documents = YamlLoader::load_from_str(source)
+        .map_err(|e| ParseError::at(e.marker().line(), format!("Invalid YAML, {}", e.info())))?;
+    let document = documents.first().ok_or(ParseError::general("The file is empty"))?;
+    let mut warnings = vec![];
+
+    let blank = symbol(&document["blank"]).map_err(|e| ParseError::general(&format!("blank: {}", e)))?;
+    let start = scalar(&document["start state"]).ok_or(ParseError::general("start state is missing"))?;
+    let table = document["table"].as_hash().ok_or(ParseError::general("table is missing"))?;
+
+    let mut description = Description::new(1, blank);
+    description.input = match &document["input"] {
+        Yaml::BadValue | Yaml::Null => None,
+        other => Some(scalar(other).ok_or(ParseError::general("input must be a string"))?),
+    };
+
+    let mut halting = HashMap::<String, bool>::new();
+    for (state, entries) in table {
+        let state = scalar(state).ok_or(ParseError::general("State names must be scalars"))?;
+        halting.insert(state, entries.is_null());
+    }
+    if !halting.contains_key(&start) {
+        warnings.push(format!("The start state {} has no entry in the table", start));
+    }
+
+    let mut index_of = HashMap::<String, usize>::new();
+    let names = std::iter::once(start.clone()).chain(table.keys().filter_map(scalar));
+    for name in names {
+        if index_of.contains_key(&name) { continue }
+        let is_final = halting.get(&name).copied().unwrap_or(true);
+        index_of.insert(name.clone(), description.add_state(name, None, is_final));
+    }
+
+    let mut intermediate = HashMap::new();
+    for (state, entries) in table {
+        let state = scalar(state).unwrap_or_default();
+        let from = index_of[&state];
+        let Some(entries) = entries.as_hash() else { continue };
+        for (key, value) in entries {
+            let at = |e: String| ParseError::general(&format!("{}: {}", state, e));
+            let reads = symbols(key).map_err(at)?;
+            let instruction = instruction(value).map_err(at)?;
+            let to = match &instruction.next {
+                None => from,
+                Some(name) => match index_of.get(name) {
+                    Some(to) => *to,
+                    None => {
+                        warnings.push(format!("{} is used but not declared, it was added as a final state", name));
+                        let to = description.add_state(name.clone(), None, true);
+                        index_of.insert(name.clone(), to);
+                        to
+                    }
+                }
+            };
+            for read in reads {
+                let write = instruction.write.unwrap_or(read);
+                if RESERVED_SYMBOLS.contains(&read) || RESERVED_SYMBOLS.contains(&write) {
+                    warnings.push(format!("{}: symbol {} or {} is reserved in labels, the instruction was dropped", state, read, write));
+                    continue;
+                }
+                description.add_quintuple(&mut intermediate, from, &[read], &[write], &[instruction.direction], to);
+            }
+        }
+    }
+
+    Ok((description, warnings))
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn name(s: &str) -> String {
+    let plain = s.starts_with(|c: char| c.is_alphabetic())
+        && !s.ends_with(' ')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ' ')
+        && !matches!(s.to_lowercase().as_str(), "true" | "false" | "null" | "yes" | "no" | "on" | "off");
+    if plain {
+        s.to_owned()
+    } else {
+        quote(s)
+    }
+}
+
+pub fn print(description: &Description) -> Result<String, String> {
+    if description.tapes != 1 { return Err("turingmachine.io only supports single tape machines".to_owned()) }
+    let mut names = description.unique_names();
+    let (quintuples, needed) = description.merged_quintuples()?;
+
+    // Every symbol that can be on the tape, helpers step back over any of them
+    let mut alphabet: Vec<char> = vec![description.blank];
+    let input = description.input.as_deref().map(label::split_input).transpose()?.unwrap_or_default();
+    let input_chars = input.iter().filter_map(|s| s.chars().next().filter(|_| s.chars().count() == 1));
+    for c in quintuples.iter().flat_map(|q| [q.read[0], q.write[0]]).chain(input_chars) {
+        if !alphabet.contains(&c) { alphabet.push(c); }
+    }
+
+    // Writes without a move go right to a helper that comes back to the target
+    let mut helper_of = HashMap::<usize, usize>::new();
+    let mut lines: Vec<(usize, char, char, &str, usize)> = vec![];
+    for q in &quintuples {
+        let (direction, to) = match q.moves[0] {
+            Move::Left => ("L", q.to),
+            Move::Right => ("R", q.to),
+            Move::Stay => {
+                let helper = *helper_of.entry(q.to).or_insert_with(|| {
+                    let mut helper = format!("{} back", names[q.to]);
+                    while names.contains(&helper) { helper.push('_'); }
+                    names.push(helper);
+                    names.len() - 1
+                });
+                ("R", helper)
+            }
+        };
+        lines.push((q.from, q.read[0], q.write[0], direction, to));
+    }
+
+    // Per state, instructions with the same effect share one line
+    let mut table: Vec<Vec<(Vec<char>, String)>> = vec![vec![]; names.len()];
+    for (from, read, write, direction, to) in lines {
+        let text = match (write != read, to != from) {
+            (false, false) => direction.to_owned(),
+            (false, true) => format!("{{{}: {}}}", direction, name(&names[to])),
+            (true, false) => format!("{{write: {}, {}}}", quote(&write.to_string()), direction),
+            (true, true) => format!("{{write: {}, {}: {}}}", quote(&write.to_string()), direction, name(&names[to])),
+        };
+        match table[from].iter_mut().find(|(_, t)| *t == text) {
+            Some((reads, _)) => reads.push(read),
+            None => table[from].push((vec![read], text)),
+        }
+    }
+    for (target, helper) in &helper_of {
+        table[*helper].push((alphabet.clone(), format!("{{L: {}}}", name(&names[*target]))));
+    }
+
+    let mut out = String::new();
+    if let Some(input) = &description.input { out.push_str(&format!("input: {}\n", quote(input))); }
+    out.push_str(&format!("blank: {}\n", quote(&description.blank.to_string())));
+    if let Some(start) = names.first() { out.push_str(&format!("start state: {}\n", name(start))); }
+    out.push_str("table:\n");
+    for (i, entries) in table.iter().enumerate() {
+        if !needed.get(i).unwrap_or(&true) { continue }
+        out.push_str(&format!("  {}:\n", name(&names[i])));
+        for (reads, text) in entries {
+            out.push_str(&format!("    {}: {}\n", symbol_key(reads), text));
+        }
+    }
+    Ok(out)
+}
+
+fn symbol_key(reads: &[char]) -> String {
+    if reads.len() == 1 {
+        quote(&reads[0].to_string())
+    } else {
+        format!("[{}]", reads.iter().map(|c| quote(&c.to_string())).collect::<Vec<_>>().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::TransitionDescription;
+
+    const SOURCE: &str = "\
+input: '1011'
+blank: ' '
+start state: right
+table:
+  right:
+    [1, 0]: R
+    ' ': {L: carry}
+  carry:
+    1: {write: 0, L}
+    [0, ' ']: {write: 1, L: done}
+  done:
+";
+
+    #[test]
+    fn round_trip() {
+        let (description, warnings) = parse(SOURCE).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(description.input.as_deref(), Some("1011"));
+        assert_eq!(description.states[0].name, "right");
+        let states: Vec<(&str, bool)> = description.states.iter().map(|s| (s.name.as_str(), s.is_final)).collect();
+        assert_eq!(states, [("right", false), ("carry", false), ("done", true), ("carry~0", false), ("done~1", false)]);
+        let transitions: Vec<(usize, &str, usize)> = description.transitions.iter().map(|t| (t.from, t.label.as_str(), t.to)).collect();
+        assert_eq!(transitions, [
+            (0, "1/R", 0), (0, "0/R", 0), (0, " /L", 1),
+            (3, "0/L", 1), (1, "1/0", 3),
+            (4, "1/L", 2), (1, "0/1", 4), (1, " /1", 4),
+        ]);
+
+        // The writes followed by a move are single instructions again
+        let (quintuples, needed) = description.merged_quintuples().unwrap();
+        assert_eq!(needed, [true, true, true, false, false]);
+        let carry: Vec<_> = quintuples.iter().filter(|q| q.from == 1).map(|q| (q.read[0], q.write[0], q.moves[0] == Move::Left, q.to)).collect();
+        assert_eq!(carry, [('1', '0', true, 1), ('0', '1', true, 2), (' ', '1', true, 2)]);
+
+        let printed = print(&description).unwrap();
+        let (again, _) = parse(&printed).unwrap();
+        assert_eq!(print(&again).unwrap(), printed);
+    }
+
+    #[test]
+    fn back_helper_reads_input_symbols() {
+        let mut description = Description::new(1, '_');
+        description.input = Some("ax".to_owned());
+        let a = description.add_state("a".to_owned(), None, false);
+        let b = description.add_state("b".to_owned(), None, true);
+        description.transitions.push(TransitionDescription { from: a, to: b, label: "a/b".to_owned() });
+        let printed = print(&description).unwrap();
+        assert!(printed.contains("['_', 'a', 'b', 'x']: {L: b}"), "{}", printed);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("blank: ' '\ntable:\n  a:\n").is_err());
+        assert!(parse("blank: ' '\nstart state: a\ntable:\n  a:\n    1: {write: 0, L, R}\n").is_err());
+        assert!(parse("blank: '  '\nstart state: a\ntable:\n  a:\n").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Undo and redo. Every change to the diagram is recorded as a command that knows
+//! both states, the editor runs it backwards to undo and forwards to redo.
+
+use egui::{Color32, Pos2};
+
+use super::arrow::Arrow;
+use super::node::Node;
+use super::project::Project;
+
+/// Older commands are forgotten past this
+const LIMIT: usize = 200;
+
+pub enum Command {
+    AddNode(Node),
+    /// The node with the arrows that went away with it
+    DeleteNode(Node, Vec<Arrow>),
+    MoveNode { id: usize, from: Pos2, to: Pos2 },
+    SetNodeLabel { id: usize, old: String, new: String },
+    /// With the color the node had before, final nodes get their own
+    SetFinal { id: usize, is_final: bool, old_color: Color32 },
+    SetHeader { id: usize, separate_header: bool },
+    /// Nodes with the color they had before
+    Recolor { old: Vec<(usize, Color32)>, new: Color32 },
+    AddArrow(Arrow),
+    DeleteArrow(Arrow),
+    AddLabel { arrow: usize, index: usize, label: String },
+    RemoveLabel { arrow: usize, index: usize, label: String },
+    EditLabel { arrow: usize, index: usize, old: String, new: String },
+    /// Done and undone together, in order
+    Group(Vec<Command>),
+    /// Everything changed at once, like an import or a load
+    Replace { before: Box<Project>, after: Box<Project>, from_text: bool },
+}
+
+impl Command {
+    /// Typing goes in one command per field instead of one per character
+    fn merge(&mut self, next: &Command) -> bool {
+        match (self, next) {
+            (Command::SetNodeLabel { id, new, .. }, Command::SetNodeLabel { id: next_id, new: next_new, .. }) if id == next_id => {
+                *new = next_new.clone();
+                true
+            }
+            (
+                Command::EditLabel { arrow, index, new, .. },
+                Command::EditLabel { arrow: next_arrow, index: next_index, new: next_new, .. }
+            ) if arrow == next_arrow && index == next_index => {
+                *new = next_new.clone();
+                true
+            }
+            (Command::Recolor { old, new }, Command::Recolor { old: next_old, new: next_new })
+                if old.iter().map(|(id, _)| id).eq(next_old.iter().map(|(id, _)| id)) => {
+                *new = *next_new;
+                true
+            }
+            (Command::Replace { after, from_text: true, .. }, Command::Replace { after: next_after, from_text: true, .. }) => {
+                *after = next_after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    done: Vec<Command>,
+    undone: Vec<Command>,
+    /// Cleared by undo, redo and `seal` so that typing afterwards starts a new command
+    can_merge: bool,
+}
+
+impl History {
+    /// A new change, what was undone can't be redone anymore
+    pub fn record(&mut self, command: Command) {
+        self.undone.clear();
+        if self.can_merge && let Some(last) = self.done.last_mut() && last.merge(&command) { return }
+        self.push_done(command);
+        self.can_merge = true;
+    }
+
+    fn push_done(&mut self, command: Command) {
+        self.done.push(command);
+        if self.done.len() > LIMIT { self.done.remove(0); }
+    }
+
+    /// Ends the last command, the next change won't merge into it
+    pub fn seal(&mut self) {
+        self.can_merge = false;
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Command> {
+        self.can_merge = false;
+        self.done.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Command> {
+        self.can_merge = false;
+        self.undone.pop()
+    }
+
+    /// Keeps a command that was just undone
+    pub fn push_undone(&mut self, command: Command) {
+        self.undone.push(command);
+    }
+
+    /// Keeps a command that was just redone
+    pub fn push_redone(&mut self, command: Command) {
+        self.push_done(command);
+        self.can_merge = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(id: usize, old: &str, new: &str) -> Command {
+        Command::SetNodeLabel { id, old: old.to_owned(), new: new.to_owned() }
+    }
+
+    fn moved(id: usize, x: f32) -> Command {
+        Command::MoveNode { id, from: Pos2::ZERO, to: Pos2::new(x, 0.0) }
+    }
+
+    /// Undoes everything, giving back the commands in the order they were done
+    fn undo_all(history: &mut History) -> Vec<Command> {
+        let mut undone = vec![];
+        while let Some(command) = history.pop_undo() { undone.insert(0, command); }
+        undone
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::default();
+        history.record(moved(0, 10.0));
+        history.record(Command::SetFinal { id: 0, is_final: true, old_color: Color32::WHITE });
+
+        let last = history.pop_undo().unwrap();
+        assert!(matches!(last, Command::SetFinal { id: 0, is_final: true, old_color: Color32::WHITE }));
+        history.push_undone(last);
+        let redone = history.pop_redo().unwrap();
+        assert!(matches!(redone, Command::SetFinal { .. }));
+        history.push_redone(redone);
+        assert!(history.pop_redo().is_none());
+        assert_eq!(undo_all(&mut history).len(), 2);
+        assert!(history.pop_undo().is_none());
+    }
+
+    #[test]
+    fn typing_merges_but_moves_do_not() {
+        let mut history = History::default();
+        history.record(label(0, "", "q"));
+        history.record(label(0, "q", "q0"));
+        history.record(label(1, "", "x"));
+        history.record(moved(1, 10.0));
+        history.record(moved(1, 20.0));
+        let done = undo_all(&mut history);
+        assert_eq!(done.len(), 4);
+        assert!(matches!(&done[0], Command::SetNodeLabel { id: 0, old, new } if old.is_empty() && new == "q0"));
+        assert!(matches!(&done[1], Command::SetNodeLabel { id: 1, .. }));
+        // Every drag is its own step
+        assert!(matches!(done[2], Command::MoveNode { to, .. } if to.x == 10.0));
+        assert!(matches!(done[3], Command::MoveNode { to, .. } if to.x == 20.0));
+
+        // Typing again after an undo or a redo starts a new step
+        let mut history = History::default();
+        history.record(label(0, "", "q"));
+        let undone = history.pop_undo().unwrap();
+        history.push_undone(undone);
+        let redone = history.pop_redo().unwrap();
+        history.push_redone(redone);
+        history.record(label(0, "q", "q0"));
+        assert_eq!(undo_all(&mut history).len(), 2);
+
+        // And so does a sealed step, like after selecting another node
+        let mut history = History::default();
+        history.record(label(0, "", "q"));
+        history.seal();
+        history.record(label(0, "q", "q0"));
+        assert_eq!(undo_all(&mut history).len(), 2);
+    }
+
+    #[test]
+    fn a_new_change_clears_what_was_undone() {
+        let mut history = History::default();
+        history.record(moved(0, 10.0));
+        history.record(moved(0, 20.0));
+        let undone = history.pop_undo().unwrap();
+        history.push_undone(undone);
+        history.record(Command::SetFinal { id: 0, is_final: true, old_color: Color32::WHITE });
+        assert!(history.pop_redo().is_none());
+        assert_eq!(undo_all(&mut history).len(), 2);
+    }
+
+    #[test]
+    fn only_the_latest_commands_are_kept() {
+        let mut history = History::default();
+        for i in 0..LIMIT + 10 { history.record(moved(0, i as f32)); }
+        let done = undo_all(&mut history);
+        assert_eq!(done.len(), LIMIT);
+        assert!(matches!(done[0], Command::MoveNode { to, .. } if to.x == 10.0));
+
+        // Redoing counts too
+        let mut history = History::default();
+        for i in 0..LIMIT { history.record(moved(0, i as f32)); }
+        let undone = history.pop_undo().unwrap();
+        history.push_undone(undone);
+        history.record(moved(1, 0.0));
+        history.push_redone(moved(2, 0.0));
+        assert_eq!(undo_all(&mut history).len(), LIMIT);
+    }
+}
@@ -0,0 +1,257 @@
+use egui::{Pos2, Vec2};
+use std::collections::VecDeque;
+
+pub const ORIGIN: Pos2 = Pos2::new(50.0, 150.0);
+pub const LAYER_SPACING: f32 = 220.0;
+pub const ROW_SPACING: f32 = 170.0;
+
+/// Top left corners closer than this could make nodes overlap
+pub const MIN_DISTANCE: f32 = 150.0;
+/// Passes of the crossing reduction in `layered`
+const SWEEPS: usize = 4;
+const FORCE_ITERATIONS: usize = 300;
+/// Keeps states without arrows from drifting away, relative to the pull of an arrow
+const GRAVITY: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LayoutKind {
+    Layered,
+    ForceDirected,
+    Circular,
+}
+
+impl LayoutKind {
+    pub const ALL: [LayoutKind; 3] = [LayoutKind::Layered, LayoutKind::ForceDirected, LayoutKind::Circular];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutKind::Layered => "Layered",
+            LayoutKind::ForceDirected => "Force-directed",
+            LayoutKind::Circular => "Circular",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            LayoutKind::Layered => "Columns from left to right by distance from the start state",
+            LayoutKind::ForceDirected => "Connected states close together, the others pushed apart",
+            LayoutKind::Circular => "States on a circle in the order they are reached",
+        }
+    }
+
+    /// Top left corners starting from `ORIGIN`
+    pub fn arrange(&self, state_count: usize, edges: &[(usize, usize)], start: usize) -> Vec<Pos2> {
+        match self {
+            LayoutKind::Layered => layered(state_count, edges, start),
+            LayoutKind::ForceDirected => force_directed(state_count, edges, start),
+            LayoutKind::Circular => circular(state_count, edges, start),
+        }
+    }
+}
+
+/// Distance of every state from `start` and the order they are reached in, the
+/// unreachable ones come last with `usize::MAX`
+fn breadth_first(state_count: usize, edges: &[(usize, usize)], start: usize) -> (Vec<usize>, Vec<usize>) {
+    let mut layer = vec![usize::MAX; state_count];
+    let mut order = Vec::with_capacity(state_count);
+    let mut queue = VecDeque::new();
+
+    if start < state_count {
+        layer[start] = 0;
+        queue.push_back(start);
+    }
+    while let Some(q) = queue.pop_front() {
+        order.push(q);
+        for (from, to) in edges {
+            if *from == q && *to < state_count && layer[*to] == usize::MAX {
+                layer[*to] = layer[q] + 1;
+                queue.push_back(*to);
+            }
+        }
+    }
+    order.extend((0..state_count).filter(|q| layer[*q] == usize::MAX));
+    (layer, order)
+}
+
+/// Places states in columns by their distance from `start`, following `edges` (from, to).
+/// States that can't be reached from `start` are put in one last column.
+pub fn layered(state_count: usize, edges: &[(usize, usize)], start: usize) -> Vec<Pos2> {
+    let (mut layer, order) = breadth_first(state_count, edges, start);
+    let last = layer.iter().filter(|l| **l != usize::MAX).max().map(|l| l + 1).unwrap_or(0);
+    for l in layer.iter_mut().filter(|l| **l == usize::MAX) { *l = last; }
+
+    let mut columns = vec![vec![]; last + 1];
+    for q in order { columns[layer[q]].push(q); }
+    reduce_crossings(&mut columns, &layer, edges);
+
+    let mut positions = vec![ORIGIN; state_count];
+    for (l, column) in columns.iter().enumerate() {
+        for (row, q) in column.iter().enumerate() {
+            positions[*q] = Pos2::new(
+                ORIGIN.x + l as f32 * LAYER_SPACING,
+                ORIGIN.y + row as f32 * ROW_SPACING
+            );
+        }
+    }
+    positions
+}
+
+/// Sorts every column by the mean row of the neighbours in the column before, then in
+/// the column after, which untangles most of the arrows between them
+fn reduce_crossings(columns: &mut [Vec<usize>], layer: &[usize], edges: &[(usize, usize)]) {
+    let mut row = vec![0.0; layer.len()];
+    for column in columns.iter() {
+        for (r, q) in column.iter().enumerate() { row[*q] = r as f32; }
+    }
+    let sort = |column: &mut Vec<usize>, neighbours: usize, row: &mut [f32]| {
+        let barycenter = |q: usize| {
+            let rows: Vec<f32> = edges.iter()
+                .filter_map(|(from, to)| if *from == q { Some(*to) } else if *to == q { Some(*from) } else { None })
+                .filter(|p| layer.get(*p) == Some(&neighbours))
+                .map(|p| row[p])
+                .collect();
+            if rows.is_empty() { row[q] } else { rows.iter().sum::<f32>() / rows.len() as f32 }
+        };
+        let mut keyed: Vec<(f32, usize)> = column.iter().map(|q| (barycenter(*q), *q)).collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        *column = keyed.into_iter().map(|(_, q)| q).collect();
+        for (r, q) in column.iter().enumerate() { row[*q] = r as f32; }
+    };
+    for _ in 0..SWEEPS {
+        for (l, column) in columns.iter_mut().enumerate().skip(1) { sort(column, l - 1, &mut row); }
+        for (l, column) in columns.iter_mut().enumerate().rev().skip(1) { sort(column, l + 1, &mut row); }
+    }
+}
+
+/// Places states on a circle in the order a search from `start` reaches them,
+/// so chains of states end up next to each other
+pub fn circular(state_count: usize, edges: &[(usize, usize)], start: usize) -> Vec<Pos2> {
+    let (_, order) = breadth_first(state_count, edges, start);
+    // Neighbours on the circle are `MIN_DISTANCE` apart
+    let radius = if state_count < 2 { 0.0 } else { MIN_DISTANCE / (2.0 * (std::f32::consts::PI / state_count as f32).sin()) };
+    let mut positions = vec![ORIGIN; state_count];
+    for (i, q) in order.into_iter().enumerate() {
+        // The start state on the left, like in the layered layout
+        let angle = std::f32::consts::PI + i as f32 * std::f32::consts::TAU / state_count as f32;
+        positions[q] = Pos2::new(radius * angle.cos(), radius * angle.sin());
+    }
+    to_origin(&mut positions);
+    positions
+}
+
+/// Arrows pull states together and every state pushes the others away, starting
+/// from the circle so the result does not depend on where the states were
+pub fn force_directed(state_count: usize, edges: &[(usize, usize)], start: usize) -> Vec<Pos2> {
+    let mut positions = circular(state_count, edges, start);
+    let ideal = LAYER_SPACING;
+    for iteration in 0..FORCE_ITERATIONS {
+        let temperature = ideal * (1.0 - iteration as f32 / FORCE_ITERATIONS as f32);
+        let center = positions.iter().fold(Vec2::ZERO, |sum, p| sum + p.to_vec2()) / state_count as f32;
+        let mut forces: Vec<Vec2> = positions.iter()
+            .map(|p| -(p.to_vec2() - center) * (p.to_vec2() - center).length() / ideal * GRAVITY)
+            .collect();
+        for a in 0..state_count {
+            for b in a + 1..state_count {
+                let delta = positions[a] - positions[b];
+                let distance = delta.length().max(1.0);
+                let push = delta / distance * ideal * ideal / distance;
+                forces[a] += push;
+                forces[b] -= push;
+            }
+        }
+        for (from, to) in edges {
+            if from == to || *from >= state_count || *to >= state_count { continue }
+            let delta = positions[*from] - positions[*to];
+            let pull = delta * delta.length() / ideal;
+            forces[*from] -= pull;
+            forces[*to] += pull;
+        }
+        for (position, force) in positions.iter_mut().zip(forces) {
+            let length = force.length();
+            if length > 0.0 { *position += force / length * length.min(temperature); }
+        }
+    }
+    remove_overlaps(&mut positions);
+    to_origin(&mut positions);
+    positions
+}
+
+/// Pushes apart states closer than `MIN_DISTANCE`
+pub fn remove_overlaps(positions: &mut [Pos2]) {
+    for _ in 0..100 {
+        let mut moved = false;
+        for a in 0..positions.len() {
+            for b in a + 1..positions.len() {
+                let delta = positions[b] - positions[a];
+                let distance = delta.length();
+                if distance >= MIN_DISTANCE { continue }
+                // States on the same spot are split along a direction of their own
+                let direction = if distance > 0.01 { delta / distance } else { Vec2::angled(b as f32) };
+                let push = direction * (MIN_DISTANCE - distance) / 2.0;
+                positions[a] -= push;
+                positions[b] += push;
+                moved = true;
+            }
+        }
+        if !moved { return }
+    }
+}
+
+fn to_origin(positions: &mut [Pos2]) {
+    let Some(min) = positions.iter().copied().reduce(|a, b| a.min(b)) else { return };
+    for position in positions.iter_mut() { *position += ORIGIN - min; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chain with a branch, a loop back to the start and two states nobody reaches
+    const STATES: usize = 8;
+    const EDGES: [(usize, usize); 7] = [(0, 1), (1, 2), (1, 3), (2, 4), (3, 4), (4, 0), (5, 5)];
+
+    fn assert_apart(positions: &[Pos2]) {
+        for a in 0..positions.len() {
+            for b in a + 1..positions.len() {
+                let distance = positions[a].distance(positions[b]);
+                assert!(distance >= MIN_DISTANCE - 0.01, "{} and {} are {} apart", a, b, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn layered_puts_states_in_columns_by_distance() {
+        let positions = layered(STATES, &EDGES, 0);
+        assert_apart(&positions);
+        assert_eq!(positions[0], ORIGIN);
+        let column = |q: usize| ((positions[q].x - ORIGIN.x) / LAYER_SPACING).round() as usize;
+        assert_eq!((0..STATES).map(column).collect::<Vec<_>>(), [0, 1, 2, 2, 3, 4, 4, 4]);
+    }
+
+    #[test]
+    fn circular_keeps_neighbours_apart() {
+        for count in [2, 3, 8, 40] {
+            let positions = circular(count, &EDGES, 0);
+            assert_apart(&positions);
+            let min = positions.iter().copied().reduce(|a, b| a.min(b)).unwrap();
+            assert!((min - ORIGIN).length() < 0.01);
+        }
+        assert_eq!(circular(1, &[], 0), [ORIGIN]);
+    }
+
+    #[test]
+    fn force_directed_does_not_overlap() {
+        assert_apart(&force_directed(STATES, &EDGES, 0));
+    }
+
+    #[test]
+    fn overlapping_states_are_pushed_apart() {
+        let mut positions = vec![ORIGIN; 5];
+        positions.push(ORIGIN + Vec2::new(10.0, 0.0));
+        positions.push(ORIGIN + Vec2::new(1000.0, 0.0));
+        remove_overlaps(&mut positions);
+        assert_apart(&positions);
+        // States already far from the others stay where they are
+        assert_eq!(positions[6], ORIGIN + Vec2::new(1000.0, 0.0));
+    }
+}
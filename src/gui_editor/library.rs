@@ -0,0 +1,404 @@
+//! Ready-made sub-machines that can be dropped in the editor, built for the symbols and
+//! tape count picked by the user. Every block starts from node 0 and stops in `done`
+//! (`equal` or `different` for the comparison).
+
+use egui::{Pos2, Vec2};
+
+use super::node::Node;
+use super::arrow::Arrow;
+use super::ui::FG;
+
+const COLUMN_SPACING: f32 = 200.0;
+const ROW_SPACING: f32 = 170.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlockKind {
+    MoveToBlank,
+    CopyBlock,
+    UnaryIncrement,
+    BinaryIncrement,
+    ShiftRight,
+    CompareAndErase,
+}
+
+impl BlockKind {
+    pub const ALL: [BlockKind; 6] = [
+        BlockKind::MoveToBlank,
+        BlockKind::CopyBlock,
+        BlockKind::UnaryIncrement,
+        BlockKind::BinaryIncrement,
+        BlockKind::ShiftRight,
+        BlockKind::CompareAndErase,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockKind::MoveToBlank => "Move to first blank",
+            BlockKind::CopyBlock => "Copy block",
+            BlockKind::UnaryIncrement => "Unary increment",
+            BlockKind::BinaryIncrement => "Binary increment",
+            BlockKind::ShiftRight => "Shift right",
+            BlockKind::CompareAndErase => "Compare and erase",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            BlockKind::MoveToBlank => "Moves right until the first blank cell",
+            BlockKind::CopyBlock => "Copies the block under the head after a blank separator",
+            BlockKind::UnaryIncrement => "Appends the first symbol to a unary number",
+            BlockKind::BinaryIncrement => "Adds one to a binary number (first symbol is 0, second is 1), needs a blank cell on its left",
+            BlockKind::ShiftRight => "Shifts the block one cell right, leaving a blank under the head",
+            BlockKind::CompareAndErase => "Compares two blocks separated by a blank and erases them, needs a marker symbol",
+        }
+    }
+}
+
+pub struct BlockParameters {
+    pub symbols: Vec<char>,
+    pub blank: char,
+    pub marker: char,
+    pub tapes: usize,
+}
+
+/// A sub-graph ready to be inserted in the editor.
+/// Node ids are local to the block (0 is the entry node) and arrows refer to them.
+pub struct Block {
+    pub nodes: Vec<Node>,
+    pub arrows: Vec<Arrow>,
+}
+
+struct BlockBuilder {
+    nodes: Vec<Node>,
+    arrows: Vec<Arrow>,
+    tapes: usize,
+    others: Vec<char>,
+}
+
+impl BlockBuilder {
+    fn new(params: &BlockParameters) -> Self {
+        let mut others = params.symbols.clone();
+        others.push(params.blank);
+        BlockBuilder {
+            nodes: vec![],
+            arrows: vec![],
+            tapes: params.tapes,
+            others,
+        }
+    }
+
+    fn state(&mut self, label: &str, column: usize, row: usize) -> usize {
+        let id = self.nodes.len();
+        let position = Pos2::new(column as f32 * COLUMN_SPACING, row as f32 * ROW_SPACING);
+        self.nodes.push(Node::new(id, label.to_owned(), position, FG, true));
+        id
+    }
+
+    /// Adds `read/action` on the first tape. When the machine has more tapes, one label is
+    /// generated for every combination of the other tapes' symbols, each rewriting what it reads.
+    fn transition(&mut self, from: usize, read: char, action: char, to: usize) {
+        let mut combinations: Vec<Vec<char>> = vec![vec![]];
+        for _ in 1..self.tapes {
+            combinations = combinations.into_iter().flat_map(|prefix| {
+                self.others.iter().map(move |s| {
+                    let mut next = prefix.clone();
+                    next.push(*s);
+                    next
+                })
+            }).collect();
+        }
+
+        let arrow_idx = match self.arrows.iter().position(|a| a.id_from_node == from && a.id_to_node == Some(to)) {
+            Some(idx) => idx,
+            None => {
+                let id = self.arrows.len();
+                self.arrows.push(Arrow::new(id, Pos2::ZERO, Pos2::ZERO, from, Some(to)));
+                id
+            }
+        };
+        for rest in combinations {
+            let rest: String = rest.into_iter().collect();
+            self.arrows[arrow_idx].add_label(format!("{}{}/{}{}", read, rest, action, rest));
+        }
+    }
+
+    fn finish(self) -> Block {
+        Block { nodes: self.nodes, arrows: self.arrows }
+    }
+}
+
+fn validate(kind: BlockKind, params: &BlockParameters) -> Result<(), String> {
+    if params.symbols.is_empty() { return Err("At least one symbol is needed".to_owned()) }
+    if params.tapes == 0 || params.tapes > 3 { return Err("Blocks support 1 to 3 tapes".to_owned()) }
+    for (i, s) in params.symbols.iter().enumerate() {
+        if matches!(s, 'L' | 'R' | '/') { return Err(format!("{} is reserved and can't be a symbol", s)) }
+        if *s == params.blank { return Err(format!("{} is the blank symbol", s)) }
+        if params.symbols[..i].contains(s) { return Err(format!("{} is repeated", s)) }
+    }
+    match kind {
+        BlockKind::BinaryIncrement if params.symbols.len() != 2 => {
+            Err("Binary increment needs exactly two symbols, 0 and 1".to_owned())
+        }
+        BlockKind::CompareAndErase if params.symbols.contains(&params.marker) || params.marker == params.blank
+            || matches!(params.marker, 'L' | 'R' | '/') => {
+            Err("The marker must be a new symbol".to_owned())
+        }
+        _ => Ok(())
+    }
+}
+
+pub fn build(kind: BlockKind, params: &BlockParameters) -> Result<Block, String> {
+    validate(kind, params)?;
+    let (sigma, b) = (params.symbols.clone(), params.blank);
+    let mut m = BlockBuilder::new(params);
+
+    match kind {
+        BlockKind::MoveToBlank => {
+            let q0 = m.state("scan", 0, 0);
+            let done = m.state("done", 1, 0);
+            for s in &sigma { m.transition(q0, *s, 'R', q0); }
+            m.transition(q0, b, b, done);
+        }
+        BlockKind::UnaryIncrement => {
+            let one = sigma[0];
+            let q0 = m.state("scan", 0, 0);
+            let done = m.state("done", 1, 0);
+            m.transition(q0, one, 'R', q0);
+            m.transition(q0, b, one, done);
+        }
+        BlockKind::BinaryIncrement => {
+            let (zero, one) = (sigma[0], sigma[1]);
+            let q0 = m.state("scan", 0, 0);
+            let carry = m.state("carry", 1, 0);
+            let wrote = m.state("wrote 0", 1, 1);
+            let done = m.state("done", 2, 0);
+            m.transition(q0, zero, 'R', q0);
+            m.transition(q0, one, 'R', q0);
+            m.transition(q0, b, 'L', carry);
+            m.transition(carry, one, zero, wrote);
+            m.transition(wrote, zero, 'L', carry);
+            m.transition(carry, zero, one, done);
+            m.transition(carry, b, one, done);
+        }
+        BlockKind::ShiftRight => {
+            let q0 = m.state("pick", 0, 0);
+            let done = m.state("done", 3, 0);
+            let mut moving = vec![];
+            let mut carrying = vec![];
+            for (i, s) in sigma.iter().enumerate() {
+                moving.push(m.state(&format!("move {}", s), 1, i + 1));
+                carrying.push(m.state(&format!("carry {}", s), 2, i + 1));
+            }
+            m.transition(q0, b, b, done);
+            for (i, s) in sigma.iter().enumerate() {
+                m.transition(q0, *s, b, moving[i]);
+                for y in sigma.iter().chain(std::iter::once(&b)) {
+                    m.transition(moving[i], *y, 'R', carrying[i]);
+                }
+                for (j, t) in sigma.iter().enumerate() {
+                    m.transition(carrying[i], *t, *s, moving[j]);
+                }
+                m.transition(carrying[i], b, *s, done);
+            }
+        }
+        BlockKind::CopyBlock => {
+            let q0 = m.state("pick", 0, 0);
+            let done = m.state("done", 0, sigma.len() + 1);
+            for (i, s) in sigma.iter().enumerate() {
+                let row = i + 1;
+                let hole = m.state(&format!("hole {}", s), 1, row);
+                let source = m.state(&format!("src {}", s), 2, row);
+                let target = m.state(&format!("dst {}", s), 3, row);
+                let back_target = m.state(&format!("back d {}", s), 4, row);
+                let back_source = m.state(&format!("back s {}", s), 5, row);
+                let restored = m.state(&format!("put {}", s), 6, row);
+
+                m.transition(q0, *s, b, hole);
+                m.transition(hole, b, 'R', source);
+                m.transition(source, b, 'R', target);
+                m.transition(target, b, *s, back_target);
+                m.transition(back_target, b, 'L', back_source);
+                m.transition(back_source, b, *s, restored);
+                m.transition(restored, *s, 'R', q0);
+                for t in &sigma {
+                    m.transition(source, *t, 'R', source);
+                    m.transition(target, *t, 'R', target);
+                    m.transition(back_target, *t, 'L', back_target);
+                    m.transition(back_source, *t, 'L', back_source);
+                }
+            }
+            m.transition(q0, b, b, done);
+        }
+        BlockKind::CompareAndErase => {
+            let x = params.marker;
+            let n = sigma.len();
+
+            let q0 = m.state("pick", 0, 0);
+            let back = m.state("back 2", 4, 0);
+            let back_first = m.state("back 1", 5, 0);
+            let check = m.state("check", 0, n + 1);
+            let check_rest = m.state("check 2", 1, n + 1);
+            let erase_eq = m.state("erase =", 2, n + 1);
+            let erase_eq_move = m.state("erase = L", 3, n + 1);
+            let equal = m.state("equal", 4, n + 1);
+            let to_end = m.state("to end", 0, n + 2);
+            let erase_2 = m.state("erase 2", 1, n + 2);
+            let erase_2_move = m.state("erase 2 L", 2, n + 2);
+            let erase_1 = m.state("erase 1", 3, n + 2);
+            let erase_1_move = m.state("erase 1 L", 4, n + 2);
+            let different = m.state("different", 5, n + 2);
+
+            for (i, s) in sigma.iter().enumerate() {
+                let row = i + 1;
+                let erased = m.state(&format!("erased {}", s), 1, row);
+                let first = m.state(&format!("in 1 {}", s), 2, row);
+                let second = m.state(&format!("in 2 {}", s), 3, row);
+
+                m.transition(q0, *s, b, erased);
+                m.transition(erased, b, 'R', first);
+                m.transition(first, b, 'R', second);
+                m.transition(second, x, 'R', second);
+                m.transition(second, *s, x, back);
+                m.transition(second, b, b, to_end);
+                for t in &sigma {
+                    m.transition(first, *t, 'R', first);
+                    if t != s { m.transition(second, *t, *t, to_end); }
+                }
+            }
+            m.transition(q0, b, b, check);
+
+            // Walk back over the second block, then over the rest of the first one
+            m.transition(back, x, 'L', back);
+            m.transition(back, b, 'L', back_first);
+            m.transition(back_first, b, 'R', q0);
+            for t in &sigma {
+                m.transition(back, *t, 'L', back);
+                m.transition(back_first, *t, 'L', back_first);
+            }
+
+            // First block exhausted, the second one must be fully marked
+            m.transition(check, b, 'R', check_rest);
+            m.transition(check_rest, x, 'R', check_rest);
+            m.transition(check_rest, b, 'L', erase_eq);
+            m.transition(erase_eq, x, b, erase_eq_move);
+            m.transition(erase_eq_move, b, 'L', erase_eq);
+            m.transition(erase_eq, b, b, equal);
+
+            // Mismatch, erase everything that is left
+            m.transition(to_end, x, 'R', to_end);
+            m.transition(to_end, b, 'L', erase_2);
+            m.transition(erase_2, x, b, erase_2_move);
+            m.transition(erase_2_move, b, 'L', erase_2);
+            m.transition(erase_2, b, 'L', erase_1);
+            m.transition(erase_1_move, b, 'L', erase_1);
+            m.transition(erase_1, b, b, different);
+            for t in &sigma {
+                m.transition(check_rest, *t, *t, to_end);
+                m.transition(to_end, *t, 'R', to_end);
+                m.transition(erase_2, *t, b, erase_2_move);
+                m.transition(erase_1, *t, b, erase_1_move);
+            }
+        }
+    }
+
+    Ok(m.finish())
+}
+
+/// Offset to apply to a block so that its entry node lands on `position`
+pub fn placement(block: &Block, position: Pos2) -> Vec2 {
+    block.nodes.first().map(|n| position - n.top_left).unwrap_or(Vec2::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui_editor::formats::Description;
+    use crate::gui_editor::ui::NodeEditor;
+    use crate::turing::{Computation, Tape};
+    use crate::turing::computation::StepFeedback;
+
+    /// Runs the block on `input` with the head on `head` until it stops, giving back the
+    /// first tape without the trailing blanks and the label of the last state
+    fn run<const K: usize>(kind: BlockKind, symbols: &str, input: &str, head: usize) -> (String, String) {
+        let params = BlockParameters { symbols: symbols.chars().collect(), blank: '*', marker: 'X', tapes: K };
+        let block = build(kind, &params).unwrap();
+        let nodes: Vec<Option<Node>> = block.nodes.into_iter().map(Some).collect();
+        let arrows: Vec<Option<Arrow>> = block.arrows.into_iter().map(Some).collect();
+        let mut description = Description::from_diagram(&nodes, &arrows, K as u8, '*');
+        description.input = Some(input.to_owned());
+        let mut editor = NodeEditor::new();
+        editor.load_description(description);
+
+        let (alphabet, m, content) = editor.build_machine::<K>().unwrap();
+        let size = content.len();
+        let mut tapes = vec![Tape::with_content(content, true)];
+        tapes.extend((1..K).map(|_| Tape::with_size(size, true)));
+        let mut c = Computation::<K>::new();
+        c.use_alphabet(alphabet);
+        c.use_machine(m);
+        c.use_tapes(tapes.try_into().ok().unwrap());
+        c.shift_head(head, 0);
+
+        for _ in 0..10_000 {
+            if let StepFeedback::NeedToStop = c.step().unwrap() { break }
+        }
+        let tape = c.tapes_content().unwrap().remove(0).cells.concat();
+        let state = nodes[c.current_state() as usize].as_ref().unwrap().label.clone();
+        (tape.trim_end_matches('*').to_owned(), state)
+    }
+
+    fn done(tape: &str) -> (String, String) {
+        (tape.to_owned(), "done".to_owned())
+    }
+
+    #[test]
+    fn move_to_blank() {
+        assert_eq!(run::<1>(BlockKind::MoveToBlank, "01", "0110", 0), done("0110"));
+    }
+
+    #[test]
+    fn copy_block() {
+        assert_eq!(run::<1>(BlockKind::CopyBlock, "ab", "abba", 0), done("abba*abba"));
+    }
+
+    #[test]
+    fn unary_increment() {
+        assert_eq!(run::<1>(BlockKind::UnaryIncrement, "1", "111", 0), done("1111"));
+        assert_eq!(run::<1>(BlockKind::UnaryIncrement, "1", "", 0), done("1"));
+    }
+
+    #[test]
+    fn binary_increment() {
+        assert_eq!(run::<1>(BlockKind::BinaryIncrement, "01", "*1011", 1), done("*1100"));
+        assert_eq!(run::<1>(BlockKind::BinaryIncrement, "01", "*11", 1), done("100"));
+    }
+
+    #[test]
+    fn shift_right() {
+        assert_eq!(run::<1>(BlockKind::ShiftRight, "ab", "abb", 0), done("*abb"));
+    }
+
+    #[test]
+    fn compare_and_erase() {
+        assert_eq!(run::<1>(BlockKind::CompareAndErase, "ab", "ab*ab", 0), ("".to_owned(), "equal".to_owned()));
+        assert_eq!(run::<1>(BlockKind::CompareAndErase, "ab", "ab*ba", 0), ("".to_owned(), "different".to_owned()));
+        assert_eq!(run::<1>(BlockKind::CompareAndErase, "ab", "ab*a", 0), ("".to_owned(), "different".to_owned()));
+    }
+
+    #[test]
+    fn other_tapes_are_left_alone() {
+        assert_eq!(run::<2>(BlockKind::UnaryIncrement, "1", "11", 0), done("111"));
+        assert_eq!(run::<3>(BlockKind::ShiftRight, "ab", "ba", 0), done("*ba"));
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let params = |symbols: &str, tapes: usize| BlockParameters { symbols: symbols.chars().collect(), blank: '*', marker: '1', tapes };
+        assert!(build(BlockKind::MoveToBlank, &params("", 1)).is_err());
+        assert!(build(BlockKind::MoveToBlank, &params("01", 4)).is_err());
+        assert!(build(BlockKind::MoveToBlank, &params("0R", 1)).is_err());
+        assert!(build(BlockKind::MoveToBlank, &params("0*", 1)).is_err());
+        assert!(build(BlockKind::BinaryIncrement, &params("012", 1)).is_err());
+        assert!(build(BlockKind::CompareAndErase, &params("01", 1)).is_err());
+    }
+}
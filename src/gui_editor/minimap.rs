@@ -0,0 +1,67 @@
+//! Overview of the whole diagram in a corner of the canvas. It shows the part the canvas
+//! is looking at, clicking or dragging on it moves the view there.
+
+use egui::{Color32, Pos2, Rect, Sense, Shape, Stroke, StrokeKind, Ui, Vec2};
+
+use super::arrow::Arrow;
+use super::node::Node;
+use super::ui::CURRENT_STATE;
+use super::viewport::Viewport;
+use super::TEXT_COLOR;
+
+const SIZE: Vec2 = Vec2::new(200.0, 140.0);
+/// Screen pixels between the minimap and the canvas border
+const MARGIN: f32 = 10.0;
+/// World units shown around the diagram
+const PADDING: f32 = 50.0;
+const BACKGROUND: Color32 = Color32::from_rgba_premultiplied(0x10, 0x10, 0x10, 0xE0);
+const ARROW_COLOR: Color32 = Color32::from_gray(0x90);
+
+/// `current` is the node the computation is on, if any
+pub fn show(ui: &mut Ui, canvas: Rect, nodes: &[Option<Node>], arrows: &[Option<Arrow>], viewport: &mut Viewport, current: Option<usize>) {
+    let map = Rect::from_min_size(canvas.max - SIZE - Vec2::splat(MARGIN), SIZE);
+    let visible = viewport.to_world_rect(canvas);
+    let id = egui::Id::new("minimap");
+    let response = ui.interact(map, id, Sense::click_and_drag());
+
+    // The bounds follow the view, so they are kept as they were when a drag started:
+    // otherwise moving the view grows the map under a still pointer and the view runs away
+    let frozen = if response.dragged() { ui.data(|d| d.get_temp::<(Pos2, f32)>(id)) } else { None };
+    let (origin, scale) = frozen.unwrap_or_else(|| {
+        let mut world = visible;
+        for node in nodes.iter().flatten() { world = world.union(node.rect()); }
+        let world = world.expand(PADDING);
+        let scale = (map.width() / world.width()).min(map.height() / world.height());
+        (map.center() - world.center().to_vec2() * scale, scale)
+    });
+    if response.dragged() {
+        ui.data_mut(|d| d.insert_temp(id, (origin, scale)));
+    } else {
+        ui.data_mut(|d| d.remove::<(Pos2, f32)>(id));
+    }
+    let to_map = |p: Pos2| origin + p.to_vec2() * scale;
+
+    let moved = response.drag_started() || response.drag_delta() != Vec2::ZERO;
+    if (response.clicked() || (response.dragged() && moved))
+        && let Some(pointer) = response.interact_pointer_pos()
+    {
+        viewport.center_on(((pointer - origin) / scale).to_pos2(), canvas);
+    }
+
+    let painter = ui.painter().with_clip_rect(map);
+    painter.rect_filled(map, 4.0, BACKGROUND);
+    for arrow in arrows.iter().flatten() {
+        let points = (0..=8).map(|i| to_map(arrow.point_at(i as f32 / 8.0))).collect();
+        painter.add(Shape::line(points, Stroke::new(1.0, ARROW_COLOR)));
+    }
+    for node in nodes.iter().flatten() {
+        let rect = Rect::from_min_max(to_map(node.rect().min), to_map(node.rect().max));
+        painter.rect_filled(rect, 1.0, node.foreground_color);
+        if current == Some(node.id) {
+            painter.rect_stroke(rect, 1.0, Stroke::new(2.0, CURRENT_STATE), StrokeKind::Outside);
+        }
+    }
+    let view = Rect::from_min_max(to_map(visible.min), to_map(visible.max));
+    painter.rect_stroke(view, 0.0, Stroke::new(1.5, TEXT_COLOR), StrokeKind::Inside);
+    painter.rect_stroke(map, 4.0, Stroke::new(1.0, ARROW_COLOR), StrokeKind::Inside);
+}
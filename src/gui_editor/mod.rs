@@ -2,13 +2,20 @@ pub mod arrow;
 pub mod node;
 pub mod ui;
 pub mod drawable;
+pub mod library;
+pub mod layout;
+pub mod formats;
+pub mod project;
+pub mod history;
+pub mod viewport;
+pub mod minimap;
+pub mod clipboard;
+pub mod arrange;
+pub mod tape_view;
 
 pub const TEXT_COLOR: egui::Color32 = egui::Color32::from_rgb(0xE6, 0xE6, 0xE6);
 pub use ui::NodeEditor as Editor;
 
-pub trait Serializable {
-    fn serialize(&self) -> String;
-}
 pub trait Deserializable {
     fn deserialize(from: &String) -> Result<Self, String> where Self: Sized;
 }
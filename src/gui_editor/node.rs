@@ -1,9 +1,11 @@
 use super::drawable::Drawable;
+use egui::emath::TSTransform;
 use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2};
 
-const BACKGROUND_COLOR: Color32 = Color32::from_rgb(0x33, 0x33, 0x33);
+pub const BACKGROUND_COLOR: Color32 = Color32::from_rgb(0x33, 0x33, 0x33);
 use super::TEXT_COLOR;
 
+#[derive(Clone)]
 pub struct Node {
     pub id: usize,
     pub label: String,
@@ -44,6 +46,20 @@ impl Node {
         )
     }
 
+    pub fn header_rect(&self) -> Rect {
+        Rect::from_points(&[
+            self.top_left,
+            Pos2::new(self.top_left.x + self.size.x, self.top_left.y + self.size.y * 0.25)
+        ])
+    }
+
+    pub fn header_text_position(&self) -> Pos2 {
+        Pos2::new(
+            self.top_left.x + 50.0,
+            self.top_left.y + 13.0
+        )
+    }
+
     pub fn get_input_edge(&self) -> Pos2 {
         let (current_size, center) = {
             let rect = self.rect();
@@ -68,60 +84,38 @@ impl Node {
 }
 
 impl Drawable for Node {
-    fn draw(&self, painter: &Painter) {
-        let whole_rect = Rect::from_points(
-            &[
-                self.top_left,
-                Pos2::new(self.top_left.x + self.size.x, self.top_left.y + self.size.y)
-            ]
-        );
+    fn draw(&self, painter: &Painter, transform: TSTransform) {
+        let zoom = transform.scaling;
+        let whole_rect = transform * self.rect();
 
-        painter.rect_filled(whole_rect, 10, BACKGROUND_COLOR);
-        painter.rect_stroke(whole_rect, 10, Stroke::new(2.5, self.foreground_color), StrokeKind::Inside);
+        painter.rect_filled(whole_rect, 10.0 * zoom, BACKGROUND_COLOR);
+        painter.rect_stroke(whole_rect, 10.0 * zoom, Stroke::new(2.5 * zoom, self.foreground_color), StrokeKind::Inside);
         
         let label_position = whole_rect.center();
-        painter.text(label_position, Align2::CENTER_CENTER, self.label.as_str(), FontId::monospace(15.0), TEXT_COLOR);
+        painter.text(label_position, Align2::CENTER_CENTER, self.label.as_str(), FontId::monospace(15.0 * zoom), TEXT_COLOR);
 
         if self.separate_header {
-            let foreground_rect = Rect::from_points(&[
-                self.top_left,
-                Pos2::new(self.top_left.x + self.size.x, self.top_left.y + self.size.y * 0.25)
-            ]);
-            painter.rect_filled(foreground_rect, 10, self.foreground_color);
-
-            let header_pos = Pos2::new(
-                self.top_left.x + 50.0,
-                self.top_left.y + 13.0
-            );
-            painter.text(header_pos, Align2::CENTER_CENTER, format!("{}", self.id), FontId::monospace(15.0), TEXT_COLOR);
+            painter.rect_filled(transform * self.header_rect(), 10.0 * zoom, self.foreground_color);
+            painter.text(transform * self.header_text_position(), Align2::CENTER_CENTER, format!("{}", self.id), FontId::monospace(15.0 * zoom), TEXT_COLOR);
         }
     }
 }
 
-impl super::Serializable for Node {
-    fn serialize(&self) -> String {
-        let fg: u32 = {
-            let x = self.foreground_color;
-
-            0u32 
-                | ((x.r() as u32) << 16) 
-                | ((x.g() as u32) << 8) 
-                | (x.b() as u32)
-        };
-        format!("{}, {}, {}, {}, {}", self.id, self.label, self.top_left, self.separate_header, fg)
-    }
-}
 impl super::Deserializable for Node {
     fn deserialize(from: &String) -> Result<Self, String> {
         let pieces: Vec<&str> = from.split(", ").collect();
 
-        let id: usize = pieces.get(0).ok_or("No id")?.parse().map_err(|_| String::from("Could not parse id"))?;
-        let label: String = pieces.get(1).ok_or("No label")?.to_string();
-        let pos_str = pieces.get(2).ok_or("No position")?.trim_matches(|c| c == '[' || c == ']');
-        let pos_vec: Vec<u32> = pos_str.split_whitespace().map(|n| n.parse::<u32>().map_err(|_| "Could not parse position".to_owned())).collect::<Result<Vec<_>, _>>()?;
-        let header: bool = pieces.get(3).ok_or("No header")?.parse().map_err(|_| String::from("Could not parse header"))?;
-        let top_left = Pos2::new(pos_vec[0] as f32, pos_vec[1] as f32);
-        let color: u32 = pieces.get(4).ok_or("No fg")?.parse().map_err(|_| String::from("Could not parse color"))?;
+        if pieces.len() < 5 { return Err(String::from("Expected id, label, position, header and color")) }
+        // Labels could contain the separator, the other fields are counted from the end
+        let n = pieces.len();
+        let id: usize = pieces[0].parse().map_err(|_| String::from("Could not parse id"))?;
+        let label: String = pieces[1..n - 3].join(", ");
+        let pos_str = pieces[n - 3].trim_matches(|c| c == '[' || c == ']');
+        let pos_vec: Vec<f32> = pos_str.split_whitespace().map(|n| n.parse::<f32>().map_err(|_| "Could not parse position".to_owned())).collect::<Result<Vec<_>, _>>()?;
+        if pos_vec.len() != 2 { return Err(String::from("Could not parse position")) }
+        let header: bool = pieces[n - 2].parse().map_err(|_| String::from("Could not parse header"))?;
+        let top_left = Pos2::new(pos_vec[0], pos_vec[1]);
+        let color: u32 = pieces[n - 1].parse().map_err(|_| String::from("Could not parse color"))?;
         let (r, g, b) = (
             ((color & 0x00FF0000) >> 16) as u8,
             ((color & 0x0000FF00) >> 8) as u8,
@@ -0,0 +1,316 @@
+//! Project files: JSON with a version number, so files saved by older editors keep loading.
+//! Files from before the JSON format, the ones starting with `Nodes = [`, are read too.
+
+use egui::{Color32, Pos2};
+use serde::{Deserialize, Serialize};
+
+use super::arrow::Arrow;
+use super::formats::ParseError;
+use super::node::Node;
+use super::ui::FG_1;
+use super::Deserializable;
+
+/// Version written by this editor, the highest one it can read
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Metadata {
+    pub name: String,
+    pub description: String,
+    /// Version of the editor that saved the file
+    pub editor_version: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub tapes: u8,
+    pub blank: char,
+    pub extend_tape_on_end: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { tapes: 1, blank: crate::turing::alphabet::DEFAULT_BLANK, extend_tape_on_end: false }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NodeData {
+    pub id: usize,
+    pub label: String,
+    pub x: f32,
+    pub y: f32,
+    pub header: bool,
+    pub color: [u8; 3],
+    pub is_final: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArrowData {
+    pub id: usize,
+    pub from: usize,
+    pub to: usize,
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Project {
+    pub version: u32,
+    #[serde(default)]
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub input: String,
+    /// Inputs kept to try the machine on
+    #[serde(default)]
+    pub saved_inputs: Vec<String>,
+    /// Ids are the index in the list, free slots are `null`
+    pub nodes: Vec<Option<NodeData>>,
+    pub arrows: Vec<Option<ArrowData>>,
+}
+
+impl Project {
+    pub fn new(nodes: &[Option<Node>], arrows: &[Option<Arrow>]) -> Self {
+        Project {
+            version: VERSION,
+            metadata: Metadata::default(),
+            settings: Settings::default(),
+            input: String::new(),
+            saved_inputs: vec![],
+            nodes: nodes.iter().map(|n| n.as_ref().map(|node| NodeData {
+                id: node.id,
+                label: node.label.clone(),
+                x: node.top_left.x,
+                y: node.top_left.y,
+                header: node.separate_header,
+                color: [node.foreground_color.r(), node.foreground_color.g(), node.foreground_color.b()],
+                is_final: node.is_final,
+            })).collect(),
+            arrows: arrows.iter().map(|a| a.as_ref().and_then(|arrow| Some(ArrowData {
+                id: arrow.id,
+                from: arrow.id_from_node,
+                to: arrow.id_to_node?,
+                labels: arrow.labels.clone(),
+            }))).collect(),
+        }
+    }
+
+    /// Reads either format
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        if source.trim_start().starts_with('{') { from_json(source) } else { from_legacy(source) }
+    }
+
+    /// What would break the editor: ids that don't match their slot, arrows pointing at
+    /// missing nodes, two arrows between the same nodes and tape counts out of range
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let exists = |id: usize| self.nodes.get(id).is_some_and(|n| n.is_some());
+        for (i, node) in self.nodes.iter().enumerate() {
+            let Some(node) = node else { continue };
+            if node.id != i { problems.push(format!("nodes[{}].id: is {} but the node is in slot {}", i, node.id, i)); }
+        }
+        let mut pairs = std::collections::HashMap::<(usize, usize), usize>::new();
+        for (i, arrow) in self.arrows.iter().enumerate() {
+            let Some(arrow) = arrow else { continue };
+            if arrow.id != i { problems.push(format!("arrows[{}].id: is {} but the arrow is in slot {}", i, arrow.id, i)); }
+            if !exists(arrow.from) { problems.push(format!("arrows[{}].from: node {} does not exist", i, arrow.from)); }
+            if !exists(arrow.to) { problems.push(format!("arrows[{}].to: node {} does not exist", i, arrow.to)); }
+            if let Some(first) = pairs.insert((arrow.from, arrow.to), i) {
+                problems.push(format!("arrows[{}]: goes between the same nodes as arrows[{}]", i, first));
+            }
+        }
+        if !(1..=3).contains(&self.settings.tapes) {
+            problems.push(format!("settings.tapes: {} tapes, only 1 to 3 are supported", self.settings.tapes));
+        }
+        problems
+    }
+
+    /// Fixes everything `problems` reports: ids follow their slot, dangling arrows are
+    /// dropped and the labels of arrows between the same nodes end up in the first one
+    pub fn repair(&mut self) {
+        for (i, node) in self.nodes.iter_mut().enumerate() {
+            if let Some(node) = node { node.id = i; }
+        }
+        let exists: Vec<bool> = self.nodes.iter().map(|n| n.is_some()).collect();
+        let mut first_of = std::collections::HashMap::<(usize, usize), usize>::new();
+        for i in 0..self.arrows.len() {
+            let Some(arrow) = &mut self.arrows[i] else { continue };
+            arrow.id = i;
+            if !exists.get(arrow.from).copied().unwrap_or(false) || !exists.get(arrow.to).copied().unwrap_or(false) {
+                self.arrows[i] = None;
+                continue;
+            }
+            match first_of.get(&(arrow.from, arrow.to)) {
+                None => { first_of.insert((arrow.from, arrow.to), i); }
+                Some(first) => {
+                    let first = *first;
+                    let labels = self.arrows[i].take().map(|a| a.labels).unwrap_or_default();
+                    if let Some(kept) = &mut self.arrows[first] {
+                        for label in labels {
+                            if !kept.labels.contains(&label) { kept.labels.push(label); }
+                        }
+                    }
+                }
+            }
+        }
+        self.settings.tapes = self.settings.tapes.clamp(1, 3);
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Could not write the project, {}", e))
+    }
+
+    /// Arrows get their ends from the nodes on the next frame
+    pub fn diagram(&self) -> (Vec<Option<Node>>, Vec<Option<Arrow>>) {
+        let nodes = self.nodes.iter().map(|n| n.as_ref().map(|data| {
+            let [r, g, b] = data.color;
+            let mut node = Node::new(data.id, data.label.clone(), Pos2::new(data.x, data.y), Color32::from_rgb(r, g, b), data.header);
+            node.is_final = data.is_final;
+            node
+        })).collect();
+        let arrows = self.arrows.iter().map(|a| a.as_ref().map(|data| {
+            let mut arrow = Arrow::new(data.id, Pos2::ZERO, Pos2::ZERO, data.from, Some(data.to));
+            arrow.labels = data.labels.clone();
+            arrow
+        })).collect();
+        (nodes, arrows)
+    }
+}
+
+fn from_json(source: &str) -> Result<Project, ParseError> {
+    let json_error = |e: serde_json::Error, path: Option<String>| {
+        // The line is reported apart, only the message is kept
+        let message = e.to_string();
+        let message = message.split(" at line ").next().unwrap_or(&message).to_owned();
+        let message = match path {
+            Some(path) if path != "." => format!("{}: {}", path, message),
+            _ => message,
+        };
+        if e.line() > 0 { ParseError::at(e.line(), message) } else { ParseError::general(&message) }
+    };
+    let value: serde_json::Value = serde_json::from_str(source).map_err(|e| json_error(e, None))?;
+    let version = value.get("version").and_then(|v| v.as_u64())
+        .ok_or(ParseError::general("version: the project has no version number"))?;
+    match version {
+        // Parsing the text again keeps line numbers in the errors
+        1 => serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(source))
+            .map_err(|e| { let path = e.path().to_string(); json_error(e.into_inner(), Some(path)) }),
+        v if v > VERSION as u64 => Err(ParseError::general(&format!(
+            "The project was saved by a newer editor (version {}), this one reads up to version {}", v, VERSION
+        ))),
+        v => Err(ParseError::general(&format!("version: unknown project version {}", v))),
+    }
+}
+
+/// The old format has a node or arrow per line, `none` for free slots, and only stores the diagram.
+/// Final nodes were only told apart by their color.
+fn from_legacy(source: &str) -> Result<Project, ParseError> {
+    let (mut nodes, mut arrows) = (vec![], vec![]);
+    let (mut in_nodes, mut in_arrows) = (false, false);
+    for (i, line) in source.lines().enumerate() {
+        let n = i + 1;
+        if line.starts_with("Nodes = [") { in_nodes = true; continue }
+        if line.starts_with("Arrows = [") { in_arrows = true; continue }
+        if line.starts_with(']') { in_nodes = false; in_arrows = false; continue }
+
+        if in_nodes {
+            nodes.push(match line {
+                "none" => None,
+                _ => {
+                    let mut node = Node::deserialize(&line.to_owned()).map_err(|e| ParseError::at(n, format!("node: {}", e)))?;
+                    node.is_final = node.foreground_color == FG_1;
+                    Some(node)
+                }
+            });
+        } else if in_arrows {
+            arrows.push(match line {
+                "none" => None,
+                _ => Some(Arrow::deserialize(&line.to_owned()).map_err(|e| ParseError::at(n, format!("arrow: {}", e)))?),
+            });
+        }
+    }
+    if nodes.is_empty() && arrows.is_empty() && !source.contains("Nodes = [") {
+        return Err(ParseError::general("Not a project file"))
+    }
+    Ok(Project::new(&nodes, &arrows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Project {
+        let mut end = Node::new(2, "end".to_owned(), Pos2::new(200.0, 50.0), FG_1, false);
+        end.is_final = true;
+        let nodes = vec![Some(Node::new(0, "start".to_owned(), Pos2::new(50.0, 50.0), Color32::RED, true)), None, Some(end)];
+        let mut arrow = Arrow::new(0, Pos2::ZERO, Pos2::ZERO, 0, Some(2));
+        arrow.labels = vec!["a/R".to_owned(), "b/a".to_owned()];
+        let mut project = Project::new(&nodes, &[Some(arrow)]);
+        project.settings.tapes = 2;
+        project.input = "ab".to_owned();
+        project.saved_inputs = vec!["ba".to_owned()];
+        project
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = sample().to_json().unwrap();
+        let project = Project::parse(&json).unwrap();
+        assert!(project.problems().is_empty());
+        assert_eq!(project.to_json().unwrap(), json);
+
+        let (nodes, arrows) = project.diagram();
+        assert!(nodes[1].is_none());
+        let end = nodes[2].as_ref().unwrap();
+        assert!(end.is_final && !end.separate_header);
+        assert_eq!(nodes[0].as_ref().unwrap().foreground_color, Color32::RED);
+        assert_eq!(arrows[0].as_ref().unwrap().labels, ["a/R", "b/a"]);
+    }
+
+    #[test]
+    fn legacy_projects_are_migrated() {
+        // Saved before the JSON format: a comma in a label and positions that are not whole
+        let source = "Nodes = [\n\
+            0, go, right, [50.5 150.25], true, 29163\n\
+            none\n\
+            2, done, [270 150], false, 15739948\n\
+            ]\n\
+            Arrows = [\n\
+            0, [150.5 200.25], [270 200], 0, 2, [,/R, a/b]\n\
+            ]";
+        let project = Project::parse(source).unwrap();
+        assert_eq!(project.version, VERSION);
+        assert!(project.problems().is_empty());
+
+        let (nodes, arrows) = project.diagram();
+        let start = nodes[0].as_ref().unwrap();
+        assert_eq!(start.label, "go, right");
+        assert_eq!(start.top_left, Pos2::new(50.5, 150.25));
+        assert!(!start.is_final && start.separate_header);
+        assert!(nodes[1].is_none());
+        assert!(nodes[2].as_ref().unwrap().is_final);
+        assert_eq!(arrows[0].as_ref().unwrap().labels, [",/R", "a/b"]);
+
+        // Saving again writes the JSON format
+        let json = project.to_json().unwrap();
+        assert!(json.contains("\"label\": \"go, right\""));
+        assert_eq!(Project::parse(&json).unwrap().to_json().unwrap(), json);
+        assert!(Project::parse("Nodes = [\n0, a, [1 x], true, 0\n]").err().is_some_and(|e| e.line == Some(2)));
+    }
+
+    #[test]
+    fn malformed() {
+        let json = sample().to_json().unwrap();
+        assert!(Project::parse(&json.replace("\"version\": 1", "\"version\": 99")).is_err());
+        assert!(Project::parse(&json.replace("\"version\": 1,", "")).is_err());
+        let error = Project::parse(&json.replace("\"tapes\": 2", "\"tapes\": \"two\"")).err().expect("tapes is a number");
+        assert!(error.message.starts_with("settings.tapes"), "{}", error);
+        assert!(Project::parse("not a project").is_err());
+
+        let dangling = Project::parse(&json.replace("\"to\": 2", "\"to\": 1")).unwrap();
+        assert_eq!(dangling.problems().len(), 1);
+    }
+}
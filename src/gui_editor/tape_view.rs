@@ -0,0 +1,155 @@
+//! Tapes drawn as rows of cells, one row per tape. A row follows its head until it is
+//! dragged or scrolled, "Follow head" brings every row back to it. While the computation
+//! waits, clicking a cell picks a symbol to write there and dragging the head moves it.
+
+use egui::{Align2, Color32, FontId, Pos2, Rect, RichText, Sense, Stroke, StrokeKind, Ui, Vec2};
+
+use crate::turing::computation::{TapeContent, TapeEdit};
+use crate::turing::RSymbol;
+use super::node::BACKGROUND_COLOR;
+use super::ui::CURRENT_STATE;
+use super::TEXT_COLOR;
+
+const MIN_CELL_WIDTH: f32 = 40.0;
+/// Width of a character of the symbol font
+const CHAR_WIDTH: f32 = 11.0;
+const CELL_HEIGHT: f32 = 40.0;
+const INDEX_HEIGHT: f32 = 16.0;
+/// Cells kept between the head and the border of a row that follows it
+const HEAD_MARGIN: f32 = 2.0;
+const CELL_BORDER: Color32 = Color32::from_gray(0x70);
+/// Cells after the end of the tape, there only if the tape grows
+const PAST_END: Color32 = Color32::from_gray(0x24);
+const INDEX_COLOR: Color32 = Color32::from_gray(0x90);
+
+#[derive(Default)]
+pub struct TapeView {
+    /// First visible cell of every row, fractions scroll smoothly
+    offsets: Vec<f32>,
+    /// Rows moved away from their head by hand
+    detached: Vec<bool>,
+    /// Tape and cell a symbol is being picked for
+    picked: Option<(usize, usize)>,
+    /// Tape whose head is being dragged
+    moving_head: Option<usize>,
+}
+
+impl TapeView {
+    /// Returns the change asked for, `editable` allows them
+    pub fn show(&mut self, ui: &mut Ui, tapes: &[TapeContent], symbols: &[RSymbol], editable: bool) -> Option<TapeEdit> {
+        if !editable {
+            self.picked = None;
+            self.moving_head = None;
+        }
+        self.offsets.resize(tapes.len(), 0.0);
+        self.detached.resize(tapes.len(), false);
+        ui.horizontal(|ui| {
+            let detached = self.detached.iter().any(|d| *d);
+            if ui.add_enabled(detached, egui::Button::new(RichText::new("Follow head").font(FontId::monospace(15.0)))).clicked() {
+                self.detached.fill(false);
+            }
+            let hint = if editable {
+                "Drag a tape to look around, click a cell to write on it, drag the head to move it"
+            } else {
+                "Drag a tape to look around, pause to edit it"
+            };
+            ui.label(RichText::new(hint).font(FontId::monospace(12.0)).color(INDEX_COLOR));
+        });
+        let mut edit = None;
+        for (i, tape) in tapes.iter().enumerate() {
+            ui.label(RichText::new(format!("Tape {}, head on cell {}", i + 1, tape.head)).font(FontId::monospace(15.0)));
+            edit = self.row(ui, i, tape, symbols, editable).or(edit);
+        }
+        edit
+    }
+
+    fn row(&mut self, ui: &mut Ui, i: usize, tape: &TapeContent, symbols: &[RSymbol], editable: bool) -> Option<TapeEdit> {
+        let widest = tape.cells.iter().map(|c| c.chars().count()).max().unwrap_or(1);
+        let cell_width = MIN_CELL_WIDTH.max(widest as f32 * CHAR_WIDTH + 14.0);
+        let (rect, response) = ui.allocate_exact_size(Vec2::new(ui.available_width(), CELL_HEIGHT + INDEX_HEIGHT), Sense::click_and_drag());
+        let visible = rect.width() / cell_width;
+        let head = tape.head as f32;
+
+        let offset = &mut self.offsets[i];
+        let cell_at = |x: f32, offset: f32| ((x - rect.left()) / cell_width + offset).floor().max(0.0) as usize;
+        let mut edit = None;
+        if editable && response.drag_started()
+            && let Some(origin) = ui.input(|input| input.pointer.press_origin())
+            && cell_at(origin.x, *offset) == tape.head
+        {
+            self.moving_head = Some(i);
+        }
+        // Where the dragged head would land
+        let mut head_target = None;
+        if self.moving_head == Some(i) {
+            head_target = ui.input(|input| input.pointer.latest_pos()).map(|pointer| cell_at(pointer.x, *offset));
+            if response.drag_stopped() {
+                edit = head_target.map(|cell| TapeEdit::MoveHead { tape: i, cell });
+                self.moving_head = None;
+            }
+        }
+        if editable && response.clicked() && let Some(pointer) = response.interact_pointer_pos() {
+            self.picked = Some((i, cell_at(pointer.x, *offset)));
+        }
+        let mut moved = if head_target.is_some() { 0.0 } else { response.drag_delta().x };
+        if response.hovered() { moved += ui.input(|input| input.smooth_scroll_delta.x); }
+        if moved != 0.0 {
+            *offset -= moved / cell_width;
+            self.detached[i] = true;
+        }
+        if !self.detached[i] {
+            let margin = HEAD_MARGIN.min((visible - 1.0) / 2.0).max(0.0);
+            if head < *offset + margin { *offset = head - margin; }
+            if head + 1.0 > *offset + visible - margin { *offset = head + 1.0 - visible + margin; }
+        }
+        *offset = offset.min(tape.cells.len().max(tape.head + 1) as f32 - 1.0).max(0.0);
+
+        let painter = ui.painter().with_clip_rect(rect);
+        let first = offset.floor() as usize;
+        let last = (*offset + visible).ceil() as usize;
+        for cell in first..=last {
+            let x = rect.left() + (cell as f32 - *offset) * cell_width;
+            let cell_rect = Rect::from_min_size(Pos2::new(x, rect.top()), Vec2::new(cell_width, CELL_HEIGHT)).shrink(1.0);
+            let shown_head = head_target.unwrap_or(tape.head);
+            let (fill, stroke) = if cell == shown_head {
+                (CURRENT_STATE.gamma_multiply(0.35), Stroke::new(2.0, CURRENT_STATE))
+            } else if cell < tape.cells.len() {
+                (BACKGROUND_COLOR, Stroke::new(1.0, CELL_BORDER))
+            } else {
+                (PAST_END, Stroke::new(1.0, PAST_END))
+            };
+            painter.rect(cell_rect, 3.0, fill, stroke, StrokeKind::Inside);
+            if let Some(symbol) = tape.cells.get(cell) {
+                painter.text(cell_rect.center(), Align2::CENTER_CENTER, symbol, FontId::monospace(18.0), TEXT_COLOR);
+            }
+            painter.text(
+                Pos2::new(cell_rect.center().x, rect.bottom() - INDEX_HEIGHT / 2.0),
+                Align2::CENTER_CENTER,
+                cell.to_string(),
+                FontId::monospace(11.0),
+                if cell == shown_head { CURRENT_STATE } else { INDEX_COLOR }
+            );
+        }
+
+        if let Some((tape_index, cell)) = self.picked && tape_index == i {
+            let popup = egui::Popup::menu(&response)
+                .open_memory(response.clicked().then_some(egui::SetOpenCommand::Bool(true)))
+                .at_pointer_fixed()
+                .show(|ui| {
+                    ui.label(RichText::new(format!("Write on cell {}", cell)).font(FontId::monospace(12.0)).color(INDEX_COLOR));
+                    let mut chosen = None;
+                    for symbol in symbols {
+                        if ui.button(RichText::new(symbol.as_str()).font(FontId::monospace(15.0))).clicked() {
+                            chosen = Some(TapeEdit::Write { tape: i, cell, symbol: symbol.clone() });
+                        }
+                    }
+                    chosen
+                });
+            match popup {
+                Some(inner) => edit = inner.inner.or(edit),
+                None => self.picked = None,
+            }
+        }
+        edit
+    }
+}
@@ -1,17 +1,36 @@
 use egui::{Color32, Pos2, Rect};
 
 use crate::turing;
-use crate::turing::computation::StepFeedback;
+use crate::turing::computation::{ManualEdit, StepFeedback, TapeContent, TapeEdit};
+use crate::turing::label;
 
-use super::Deserializable;
-use super::Serializable;
 use super::node::Node;
 use super::arrow::Arrow;
 use super::drawable::Drawable;
+use super::library::{self, BlockKind, BlockParameters};
+use super::formats::{self, Description, ImportResult, ParseError};
+use super::project::{Metadata, Project, Settings};
+use super::history::{Command, History};
+use super::clipboard;
+use super::viewport::Viewport;
+use super::minimap;
+use super::tape_view::TapeView;
+use super::layout::{self, LayoutKind};
+use super::arrange::{self, Alignment};
 pub const FG: Color32 = Color32::from_rgb(0x00, 0x71, 0xEB);
 pub const FG_1: Color32 = Color32::from_rgb(0xF0, 0x2C, 0x2C);
+/// Distance on screen within which a dragged node snaps in line with another
+const GUIDE_TOLERANCE: f32 = 6.0;
+/// How far a pasted or duplicated node lands from the original
+const PASTE_OFFSET: egui::Vec2 = egui::vec2(30.0, 30.0);
+/// Marks the state a computation is on
+pub const CURRENT_STATE: Color32 = Color32::from_rgb(0xF0, 0xC0, 0x2C);
+const FLASH_SECONDS: f64 = 0.6;
+const SELECTION: Color32 = Color32::from_rgb(0x8C, 0xC8, 0xFF);
+const ERROR_LINE: Color32 = Color32::from_rgb(0x6B, 0x1A, 0x1A);
 const BG: [Color32; 2] = [Color32::TRANSPARENT, Color32::from_rgb(0x25, 0x25, 0x25)];
 use super::{Alphabet, Computation, TuringMachine, Tape};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
 
 pub struct NodeEditor {
@@ -52,11 +71,61 @@ pub struct NodeEditor {
     rx: Option<std::sync::mpsc::Receiver<()>>,
     can_reset: bool,
 
-    last_tape: Option<String>,
+    /// Tapes seen on the last frame the computation could be read
+    last_tapes: Vec<TapeContent>,
+    tape_view: TapeView,
+    /// Tape changes made by hand during the computation
+    last_edits: Vec<ManualEdit>,
 
     clear_outcome: Option<()>,
 
     extend_tape_on_end: bool,
+
+    library_symbols: String,
+    library_marker: String,
+
+    text_view: bool,
+    text_source: String,
+    text_synced: String,
+    text_error: Option<ParseError>,
+
+    metadata: Metadata,
+    saved_inputs: Vec<String>,
+    /// Project that failed the checks, waiting for the user to repair it or give up
+    pending_repair: Option<(Project, Vec<String>)>,
+
+    history: History,
+    /// Where the dragged node was when the drag started
+    drag_start: Option<Pos2>,
+    /// Where the dragged node would be without snapping
+    drag_raw: Pos2,
+    /// Selected nodes moving along with the dragged one
+    drag_group: Vec<usize>,
+    show_grid: bool,
+    snap_to_grid: bool,
+    show_guides: bool,
+    /// Nodes picked with shift-click or a rectangle, `selected_node_id` is only set when there is one
+    selected_nodes: BTreeSet<usize>,
+    /// World position where the selection rectangle started
+    band_start: Option<Pos2>,
+    group_color: Color32,
+    /// Pastes since the last copy, each one lands a bit further
+    pastes: u32,
+
+    viewport: Viewport,
+    /// Screen area of the canvas in the last frame
+    canvas_rect: Rect,
+    /// Node the computation was on when last seen, the worker thread may hold the lock
+    current_state: Option<usize>,
+    /// Last transition of the computation as state, symbols read and next state
+    fired: Option<(usize, String, usize)>,
+    /// States visited before the current one, the most recent last
+    trail: Vec<usize>,
+    /// How many of the visited states stay highlighted
+    trail_length: usize,
+    transition_count: usize,
+    /// When the last transition was seen, its label flashes for `FLASH_SECONDS`
+    fired_at: f64,
 }
 
 impl NodeEditor {
@@ -96,27 +165,152 @@ impl NodeEditor {
             rx: None,
             can_reset: true,
 
-            last_tape: None,
+            last_tapes: vec![],
+            tape_view: TapeView::default(),
+            last_edits: vec![],
 
             clear_outcome: None,
 
             extend_tape_on_end: false,
+
+            library_symbols: String::from("01"),
+            library_marker: String::from("X"),
+
+            text_view: false,
+            text_source: String::new(),
+            text_synced: String::new(),
+            text_error: None,
+
+            metadata: Metadata::default(),
+            saved_inputs: vec![],
+            pending_repair: None,
+
+            history: History::default(),
+            drag_start: None,
+            drag_raw: Pos2::ZERO,
+            drag_group: vec![],
+            show_grid: false,
+            snap_to_grid: false,
+            show_guides: true,
+            selected_nodes: BTreeSet::new(),
+            band_start: None,
+            group_color: FG,
+            pastes: 0,
+
+            viewport: Viewport::default(),
+            canvas_rect: Rect::EVERYTHING,
+            current_state: None,
+            fired: None,
+            trail: vec![],
+            trail_length: 5,
+            transition_count: 0,
+            fired_at: 0.0,
         }
     }
 
     pub fn delete_node(&mut self, node_id: usize) {
-        if node_id >= self.nodes.len() { return }
-        self.nodes[node_id] = None;
+        if let Some(command) = self.remove_node(node_id) { self.history.record(command); }
+    }
+
+    /// Deletes the nodes as one step of the history
+    pub fn delete_nodes(&mut self, node_ids: &[usize]) {
+        let removed: Vec<Command> = node_ids.iter().filter_map(|id| self.remove_node(*id)).collect();
+        if !removed.is_empty() { self.history.record(Command::Group(removed)); }
+    }
+
+    /// Takes the node away with its arrows, the command puts them back
+    fn remove_node(&mut self, node_id: usize) -> Option<Command> {
+        if node_id >= self.nodes.len() { return None }
+        let node = self.nodes[node_id].take()?;
+        let mut removed = vec![];
         for maybe_arrow in &mut self.arrows {
             if let Some(arrow) = maybe_arrow {
                 if arrow.id_from_node == node_id || arrow.id_to_node.unwrap() == node_id {
-                    *maybe_arrow = None;
+                    removed.extend(maybe_arrow.take());
                 }
             }
         }
+        Some(Command::DeleteNode(node, removed))
+    }
+
+    /// Makes every node final, or none of them when they all are already
+    fn toggle_final(&mut self, node_ids: &[usize]) {
+        let is_final = !node_ids.iter().all(|id| self.nodes.get(*id).is_some_and(|n| n.as_ref().is_some_and(|n| n.is_final)));
+        let mut changed = vec![];
+        for id in node_ids {
+            if let Some(Some(node)) = self.nodes.get_mut(*id) && node.is_final != is_final {
+                changed.push(Command::SetFinal { id: *id, is_final, old_color: node.foreground_color });
+                node.is_final = is_final;
+                node.foreground_color = if is_final { FG_1 } else { FG };
+            }
+        }
+        if !changed.is_empty() {
+            self.history.record(Command::Group(changed));
+            self.can_reset = true;
+        }
+    }
+
+    /// Moves every selected node by what `arrange` gives for the rects, in selection order
+    fn arrange_selection(&mut self, arrange: impl FnOnce(&[Rect]) -> Vec<egui::Vec2>) {
+        let ids: Vec<usize> = self.selected_nodes.iter().copied().filter(|id| self.nodes.get(*id).is_some_and(|n| n.is_some())).collect();
+        let rects: Vec<Rect> = ids.iter().filter_map(|id| self.nodes[*id].as_ref().map(|n| n.rect())).collect();
+        let mut moves = vec![];
+        for (id, delta) in ids.into_iter().zip(arrange(&rects)) {
+            if delta == egui::Vec2::ZERO { continue }
+            if let Some(Some(node)) = self.nodes.get_mut(id) {
+                let from = node.top_left;
+                node.change_position(delta);
+                moves.push(Command::MoveNode { id, from, to: node.top_left });
+            }
+        }
+        if !moves.is_empty() { self.history.record(Command::Group(moves)); }
+    }
+
+    fn copy_selection(&mut self, ctx: &egui::Context) {
+        if self.selected_nodes.is_empty() { return }
+        let (nodes, arrows) = clipboard::collect(&self.nodes, &self.arrows, &self.selected_nodes);
+        match clipboard::to_text(&nodes, &arrows) {
+            Ok(text) => {
+                ctx.copy_text(text);
+                self.pastes = 0;
+            }
+            Err(e) => self.popup_string = Some(e),
+        }
+    }
+
+    fn cut_selection(&mut self, ctx: &egui::Context) {
+        self.copy_selection(ctx);
+        let ids: Vec<usize> = self.selected_nodes.iter().copied().collect();
+        self.delete_nodes(&ids);
+        self.select_nodes(BTreeSet::new());
+    }
+
+    /// Text that is not a copied diagram is ignored
+    fn paste(&mut self, text: &str) {
+        let Some((nodes, arrows)) = clipboard::from_text(text) else { return };
+        self.pastes += 1;
+        let ids = self.insert_sub_graph(nodes, arrows, PASTE_OFFSET * self.pastes as f32);
+        self.select_nodes(ids.into_iter().collect());
+    }
+
+    fn duplicate_selection(&mut self) {
+        let (nodes, arrows) = clipboard::collect(&self.nodes, &self.arrows, &self.selected_nodes);
+        let ids = self.insert_sub_graph(nodes, arrows, PASTE_OFFSET);
+        self.select_nodes(ids.into_iter().collect());
+    }
+
+    fn recolor(&mut self, node_ids: &[usize], color: Color32) {
+        let mut old = vec![];
+        for id in node_ids {
+            if let Some(Some(node)) = self.nodes.get_mut(*id) {
+                old.push((*id, node.foreground_color));
+                node.foreground_color = color;
+            }
+        }
+        self.history.record(Command::Recolor { old, new: color });
     }
 
-    pub fn insert_new_node(&mut self, mut node: Node) {
+    pub fn insert_new_node(&mut self, mut node: Node) -> usize {
         let mut new_node_id = self.nodes.len();
         for (i, maybe_node) in self.nodes.iter().enumerate() {
             if maybe_node.is_none() {
@@ -127,47 +321,394 @@ impl NodeEditor {
         node.id = new_node_id;
         if new_node_id == self.nodes.len() { self.nodes.push(Some(node)) }
         else { self.nodes[new_node_id] = Some(node) }
+        new_node_id
     }
 
-    pub fn delete_arrow(&mut self, arrow_id: usize) {
-        if arrow_id >= self.arrows.len() { return }
-        self.arrows[arrow_id] = None;
+    /// Inserts nodes and arrows coming from somewhere else, giving them fresh ids.
+    /// Arrows are expected to refer to the ids the nodes had before insertion
+    pub fn insert_sub_graph(&mut self, nodes: Vec<Node>, arrows: Vec<Arrow>, offset: egui::Vec2) -> Vec<usize> {
+        let mut new_ids = std::collections::HashMap::<usize, usize>::new();
+        let mut added = vec![];
+        for mut node in nodes {
+            let old_id = node.id;
+            node.change_position(offset);
+            let id = self.insert_new_node(node);
+            new_ids.insert(old_id, id);
+            added.extend(self.nodes[id].clone().map(Command::AddNode));
+        }
+        for mut arrow in arrows {
+            let (Some(from), Some(to)) = (
+                new_ids.get(&arrow.id_from_node),
+                arrow.id_to_node.and_then(|id| new_ids.get(&id))
+            ) else { continue };
+            arrow.id_from_node = *from;
+            arrow.id_to_node = Some(*to);
+            let id = self.insert_new_arrow(arrow);
+            added.extend(self.arrows[id].clone().map(Command::AddArrow));
+        }
+        if !added.is_empty() { self.history.record(Command::Group(added)); }
+        self.can_reset = true;
+        let mut ids: Vec<usize> = new_ids.into_values().collect();
+        ids.sort();
+        ids
     }
-    fn dispatch_only_output(&mut self) -> Option<String> {
-        match self.n_tapes {
-            1 => {
-                if let Ok(mut mtx) = self.computations.0.try_lock() {
-                    if let Ok(out) = mtx.output_all() {
-                        Some(out)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
+
+    fn blank_symbol(&self) -> char {
+        self.def_blank.chars().next().unwrap_or(super::super::alphabet::DEFAULT_BLANK)
+    }
+
+    /// The blank field takes a single character, symbols longer than that can't be blank
+    fn blank_error(&self) -> Option<String> {
+        (self.def_blank.chars().count() > 1).then(|| format!("The blank symbol '{}' has to be a single character", self.def_blank))
+    }
+
+    pub fn describe(&self) -> Description {
+        let mut description = Description::from_diagram(&self.nodes, &self.arrows, self.n_tapes, self.blank_symbol());
+        if !self.input.is_empty() { description.input = Some(self.input.clone()); }
+        description
+    }
+
+    /// Replaces the whole diagram and the machine settings
+    pub fn load_description(&mut self, mut description: Description) {
+        self.discard_computation();
+        self.n_tapes = description.tapes;
+        self.def_blank = String::from(description.blank);
+        if let Some(input) = description.input.take() { self.input = input; }
+        let (nodes, arrows) = description.into_diagram();
+        self.nodes = nodes;
+        self.arrows = arrows;
+        self.select_nodes(BTreeSet::new());
+        self.dragging_arrow = None;
+        self.can_reset = true;
+    }
+
+    /// Brings the diagram in line with edited text. States are matched by name, or by place
+    /// when renamed, so their node keeps its id, color and header; only what changed is
+    /// added or removed. The computation goes on unless the tape count or the start changes.
+    fn patch_from_description(&mut self, mut description: Description) {
+        let old_ids: Vec<usize> = formats::state_order(&self.nodes).iter().map(|n| n.id).collect();
+        let old_names = self.describe().unique_names();
+        let new_names = description.unique_names();
+        let mut kept: Vec<Option<usize>> = new_names.iter()
+            .map(|name| old_names.iter().position(|old| old == name).map(|i| old_ids[i]))
+            .collect();
+        let mut renamed = vec![false; kept.len()];
+        for i in 0..kept.len() {
+            if kept[i].is_none() && let Some(id) = old_ids.get(i) && !kept.contains(&Some(*id)) {
+                kept[i] = Some(*id);
+                renamed[i] = true;
+            }
+        }
+        if description.tapes != self.n_tapes || kept.first() != Some(&Some(0)) {
+            self.load_description(description);
+            return;
+        }
+
+        self.def_blank = String::from(description.blank);
+        if let Some(input) = description.input.take() { self.input = input; }
+        for id in old_ids {
+            if !kept.contains(&Some(id)) { self.remove_node(id); }
+        }
+        let mut node_of = vec![];
+        for (i, state) in description.states.iter().enumerate() {
+            let id = match kept[i] {
+                Some(id) => id,
+                None => {
+                    let position = state.position.unwrap_or_else(|| self.new_node_position());
+                    self.insert_new_node(Node::new(0, state.name.clone(), position, FG, true))
                 }
-            },
-            2 => {
-                if let Ok(mut mtx) = self.computations.1.try_lock() {
-                    if let Ok(out) = mtx.output_all() {
-                        Some(out)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
+            };
+            if let Some(Some(node)) = self.nodes.get_mut(id) {
+                if renamed[i] { node.label = state.name.clone(); }
+                if let Some(position) = state.position { node.top_left = position; }
+                if node.is_final != state.is_final {
+                    node.is_final = state.is_final;
+                    node.foreground_color = if state.is_final { FG_1 } else { FG };
                 }
-            },
-            3 => {
-                if let Ok(mut mtx) = self.computations.2.try_lock() {
-                    if let Ok(out) = mtx.output_all() {
-                        Some(out)
-                    } else {
-                        None
+            }
+            node_of.push(id);
+        }
+
+        let mut labels = HashMap::<(usize, usize), Vec<String>>::new();
+        let mut pairs = vec![];
+        for t in description.transitions {
+            let pair = (node_of[t.from], node_of[t.to]);
+            if !labels.contains_key(&pair) { pairs.push(pair); }
+            labels.entry(pair).or_default().push(t.label);
+        }
+        for maybe_arrow in &mut self.arrows {
+            let Some(arrow) = maybe_arrow else { continue };
+            match arrow.id_to_node.and_then(|to| labels.remove(&(arrow.id_from_node, to))) {
+                Some(new_labels) => if arrow.labels != new_labels { arrow.labels = new_labels; },
+                None => *maybe_arrow = None,
+            }
+        }
+        for (from, to) in pairs {
+            let Some(new_labels) = labels.remove(&(from, to)) else { continue };
+            let mut arrow = Arrow::new(0, Pos2::ZERO, Pos2::ZERO, from, Some(to));
+            arrow.labels = new_labels;
+            self.insert_new_arrow(arrow);
+        }
+        self.dragging_arrow = None;
+        self.can_reset = true;
+    }
+
+    fn import_with(&mut self, title: &str, extensions: &[&str], parse: impl Fn(&str) -> ImportResult) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title(title)
+            .add_filter(title, extensions)
+            .pick_file()
+        else { return };
+        match std::fs::read_to_string(path) {
+            Ok(source) => match parse(&source) {
+                Ok((description, warnings)) => {
+                    self.replace_with(|editor| editor.load_description(description), false);
+                    if !warnings.is_empty() {
+                        self.popup_string = Some(format!("Imported with warnings:\n{}", warnings.join("\n")));
                     }
-                } else {
-                    None
                 }
+                Err(e) => self.popup_string = Some(e.to_string()),
             },
+            Err(_) => self.popup_string = Some("Could not open file".to_owned()),
+        }
+    }
+
+    fn export_with(&mut self, title: &str, file_name: &str, extensions: &[&str], print: impl Fn(&Description) -> Result<String, String>) {
+        if !self.nodes.first().is_some_and(|n| n.is_some()) {
+            self.popup_string = Some("The start state was deleted, there is nothing to export".to_owned());
+            return;
+        }
+        let export = print(&self.describe());
+        self.save_export(title, file_name, extensions, export);
+    }
+
+    fn save_export(&mut self, title: &str, file_name: &str, extensions: &[&str], export: Result<String, String>) {
+        let export = match export {
+            Ok(export) => export,
+            Err(e) => { self.popup_string = Some(e); return }
+        };
+        if let Some(path) = rfd::FileDialog::new()
+            .set_title(title)
+            .set_file_name(file_name)
+            .add_filter(title, extensions)
+            .save_file()
+            && std::fs::write(path, export).is_err()
+        {
+            self.popup_string = Some("Could not write file".to_owned());
+        }
+    }
+
+    /// States and transitions of the current computation, states are node ids
+    /// so they go through the same indices used by `describe`.
+    /// `None` while the running computation holds the lock.
+    fn last_run(&mut self) -> Option<formats::dot::Run> {
+        let visited = self.dispatch_visited()?;
+        let index_of = formats::state_indices(&self.nodes);
+        let mut run = formats::dot::Run::default();
+        run.states.extend(index_of.get(&0));
+        for (q, read, t) in visited {
+            let (Some(from), Some(to)) = (index_of.get(&(q as usize)), index_of.get(&(t as usize))) else { continue };
+            run.states.extend([*from, *to]);
+            run.transitions.insert((*from, read));
+        }
+        Some(run)
+    }
+
+    /// Text half of the split view. Diagram changes rewrite the text,
+    /// text changes that parse are patched into the diagram
+    fn show_text_view(&mut self, ui: &mut egui::Ui) {
+        let current = formats::text::print(&self.describe());
+        if current != self.text_synced {
+            self.text_source = current.clone();
+            self.text_synced = current;
+            self.text_error = None;
+        }
+
+        ui.heading(egui::RichText::new("Machine text").font(egui::FontId::monospace(20.0)));
+        ui.separator();
+        if let Some(e) = &self.text_error {
+            ui.label(egui::RichText::new(e.to_string()).font(egui::FontId::monospace(15.0)).color(FG_1));
+            ui.separator();
+        }
+
+        let error_line = self.text_error.as_ref().and_then(|e| e.line);
+        let mut layouter = |ui: &egui::Ui, text: &dyn egui::TextBuffer, wrap_width: f32| {
+            let mut job = egui::text::LayoutJob::default();
+            for (i, line) in text.as_str().split_inclusive('\n').enumerate() {
+                let mut format = egui::TextFormat::simple(egui::FontId::monospace(15.0), super::TEXT_COLOR);
+                if error_line == Some(i + 1) { format.background = ERROR_LINE; }
+                job.append(line, 0.0, format);
+            }
+            job.wrap.max_width = wrap_width;
+            ui.fonts(|f| f.layout_job(job))
+        };
+        let response = egui::ScrollArea::vertical().id_salt(7).show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.text_source)
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(30)
+                    .layouter(&mut layouter)
+            )
+        }).inner;
+
+        if response.lost_focus() { self.history.seal(); }
+        if response.changed() {
+            match formats::text::parse(&self.text_source) {
+                Ok(description) => {
+                    let (selected_nodes, selected_arrow) = (self.selected_nodes.clone(), self.selected_arrow_id);
+                    self.replace_with(|editor| editor.patch_from_description(description), true);
+                    self.selected_nodes = selected_nodes;
+                    self.selected_arrow_id = selected_arrow;
+                    self.keep_valid_selection();
+                    self.text_synced = formats::text::print(&self.describe());
+                    self.text_error = None;
+                }
+                Err(e) => self.text_error = Some(e),
+            }
+        }
+    }
+
+    fn insert_from_library(&mut self, kind: BlockKind) {
+        let params = BlockParameters {
+            symbols: self.library_symbols.chars().filter(|c| !c.is_whitespace()).collect(),
+            blank: self.blank_symbol(),
+            marker: self.library_marker.chars().next().unwrap_or('X'),
+            tapes: self.n_tapes as usize,
+        };
+        match library::build(kind, &params) {
+            Ok(block) => {
+                let offset = library::placement(&block, self.new_node_position());
+                self.insert_sub_graph(block.nodes, block.arrows, offset);
+            }
+            Err(e) => self.popup_string = Some(e),
+        }
+    }
+
+    /// The usual spot for new nodes when it is on screen, the top left of the canvas otherwise
+    fn new_node_position(&self) -> Pos2 {
+        let visible = self.viewport.to_world_rect(self.canvas_rect);
+        let mut position = if visible.contains(STARTING_POSITION) { STARTING_POSITION } else { visible.min + egui::vec2(25.0, 25.0) };
+        // Next to the nodes already there instead of on top of them
+        while self.nodes.iter().flatten().any(|n| n.rect().expand(10.0).intersects(Rect::from_min_size(position, NODE_SIZE))) {
+            position.x += NODE_SIZE.x + 20.0;
+        }
+        position
+    }
+
+    /// Rearranges the selected nodes, or the whole diagram when less than two are selected
+    fn auto_layout(&mut self, kind: LayoutKind) {
+        let whole = self.selected_nodes.len() < 2;
+        let ids: Vec<usize> = if whole {
+            self.nodes.iter().flatten().map(|n| n.id).collect()
+        } else {
+            self.selected_nodes.iter().copied().collect()
+        };
+        if ids.is_empty() { return }
+        let index: HashMap<usize, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let edges: Vec<(usize, usize)> = self.arrows.iter().flatten()
+            .filter_map(|a| Some((*index.get(&a.id_from_node)?, *index.get(&a.id_to_node?)?)))
+            .collect();
+        // The machine starts on node 0, a selection starts where no arrow comes in
+        let start = index.get(&0).copied()
+            .or_else(|| (0..ids.len()).find(|q| !edges.iter().any(|(from, to)| to == q && from != q)))
+            .unwrap_or(0);
+        let before: Vec<Pos2> = ids.iter().map(|id| self.nodes[*id].as_ref().map_or(Pos2::ZERO, |n| n.top_left)).collect();
+        let mut after = kind.arrange(ids.len(), &edges, start);
+
+        if !whole {
+            // The selection stays where it was and moves right of the nodes it would cover
+            let anchor = before.iter().copied().reduce(|a, b| a.min(b)).unwrap_or(layout::ORIGIN);
+            let mut offset = anchor - layout::ORIGIN;
+            let others: Vec<Rect> = self.nodes.iter().flatten()
+                .filter(|n| !self.selected_nodes.contains(&n.id))
+                .map(|n| n.rect().expand(20.0))
+                .collect();
+            for _ in 0..=others.len() {
+                let covered = after.iter()
+                    .flat_map(|p| others.iter().filter(move |r| r.intersects(Rect::from_min_size(*p + offset, NODE_SIZE))))
+                    .map(|r| r.max.x)
+                    .reduce(f32::max);
+                let Some(right) = covered else { break };
+                let left = after.iter().map(|p| p.x + offset.x).reduce(f32::min).unwrap_or(right);
+                offset.x += right - left;
+            }
+            for p in &mut after { *p += offset; }
+        }
+
+        let mut moves = vec![];
+        for ((id, from), to) in ids.iter().zip(before).zip(after) {
+            if from == to { continue }
+            if let Some(Some(node)) = self.nodes.get_mut(*id) { node.top_left = to; }
+            moves.push(Command::MoveNode { id: *id, from, to });
+        }
+        if !moves.is_empty() { self.history.record(Command::Group(moves)); }
+        if whole { self.fit_all(); }
+    }
+
+    /// Zooms and pans so that the whole diagram is on the canvas
+    fn fit_all(&mut self) {
+        let mut bounds = Rect::NOTHING;
+        for node in self.nodes.iter().flatten() { bounds = bounds.union(node.rect()); }
+        for arrow in self.arrows.iter().flatten() {
+            for i in 0..=8 { bounds.extend_with(arrow.point_at(i as f32 / 8.0)); }
+        }
+        if bounds.is_positive() { self.viewport.fit(bounds, self.canvas_rect) } else { self.viewport = Viewport::default() }
+    }
+
+    pub fn delete_arrow(&mut self, arrow_id: usize) {
+        if arrow_id >= self.arrows.len() { return }
+        if let Some(arrow) = self.arrows[arrow_id].take() {
+            self.history.record(Command::DeleteArrow(arrow));
+        }
+    }
+    fn dispatch_symbols(&mut self) -> Option<Vec<turing::RSymbol>> {
+        match self.n_tapes {
+            1 => { self.computations.0.try_lock().ok().map(|c| c.symbols()) }
+            2 => { self.computations.1.try_lock().ok().map(|c| c.symbols()) }
+            3 => { self.computations.2.try_lock().ok().map(|c| c.symbols()) }
+            _ => panic!("Impossible")
+        }
+    }
+    fn dispatch_edit_tape(&mut self, edit: TapeEdit) -> Result<(), String> {
+        match self.n_tapes {
+            1 => { self.computations.0.lock().unwrap().edit_tape(edit) }
+            2 => { self.computations.1.lock().unwrap().edit_tape(edit) }
+            3 => { self.computations.2.lock().unwrap().edit_tape(edit) }
+            _ => panic!("Impossible")
+        }
+    }
+    /// `None` while a running computation holds the lock
+    fn dispatch_manual_edits(&mut self) -> Option<Vec<ManualEdit>> {
+        match self.n_tapes {
+            1 => { self.computations.0.try_lock().ok().map(|c| c.manual_edits().to_vec()) }
+            2 => { self.computations.1.try_lock().ok().map(|c| c.manual_edits().to_vec()) }
+            3 => { self.computations.2.try_lock().ok().map(|c| c.manual_edits().to_vec()) }
+            _ => panic!("Impossible")
+        }
+    }
+    /// `None` while a running computation holds the lock
+    fn dispatch_tapes(&mut self) -> Option<Vec<TapeContent>> {
+        match self.n_tapes {
+            1 => { self.computations.0.try_lock().ok().and_then(|c| c.tapes_content().ok()) }
+            2 => { self.computations.1.try_lock().ok().and_then(|c| c.tapes_content().ok()) }
+            3 => { self.computations.2.try_lock().ok().and_then(|c| c.tapes_content().ok()) }
+            _ => panic!("Impossible")
+        }
+    }
+    /// `None` while a running computation holds the lock
+    fn dispatch_progress(&mut self) -> Option<turing::computation::Progress> {
+        match self.n_tapes {
+            1 => { self.computations.0.try_lock().ok().map(|c| c.progress()) }
+            2 => { self.computations.1.try_lock().ok().map(|c| c.progress()) }
+            3 => { self.computations.2.try_lock().ok().map(|c| c.progress()) }
+            _ => panic!("Impossible")
+        }
+    }
+    fn dispatch_visited(&mut self) -> Option<Vec<(u8, String, u8)>> {
+        match self.n_tapes {
+            1 => { self.computations.0.try_lock().ok().map(|c| c.visited_transitions()) }
+            2 => { self.computations.1.try_lock().ok().map(|c| c.visited_transitions()) }
+            3 => { self.computations.2.try_lock().ok().map(|c| c.visited_transitions()) }
             _ => panic!("Impossible")
         }
     }
@@ -234,7 +775,7 @@ impl NodeEditor {
     }
 
 
-    pub fn insert_new_arrow(&mut self, mut arrow: Arrow) {
+    pub fn insert_new_arrow(&mut self, mut arrow: Arrow) -> usize {
         let mut new_arrow_id = self.arrows.len();
         for (i, maybe_arrow) in self.arrows.iter().enumerate() {
             if maybe_arrow.is_none() {
@@ -245,118 +786,231 @@ impl NodeEditor {
         arrow.id = new_arrow_id;
         if new_arrow_id == self.arrows.len() { self.arrows.push(Some(arrow)) }
         else { self.arrows[new_arrow_id] = Some(arrow) }
+        new_arrow_id
+    }
+
+    /// Runs `change` as one step of the history, for changes that touch the whole project
+    fn replace_with(&mut self, change: impl FnOnce(&mut Self), from_text: bool) {
+        let before = Box::new(self.project());
+        change(self);
+        let after = Box::new(self.project());
+        self.history.record(Command::Replace { before, after, from_text });
     }
 
-    fn from_serialized(&mut self, ser: std::fs::File) -> Result<(), String> {
-        use std::io::{BufRead, BufReader};
-        let reader = BufReader::new(ser);
-        self.arrows.drain(..);
-        self.nodes.drain(..);
-        
-        let lines = reader.lines().into_iter();
+    fn undo(&mut self) {
+        if let Some(command) = self.history.pop_undo() {
+            self.apply(&command, false);
+            self.history.push_undone(command);
+        }
+    }
 
-        let (mut in_nodes, mut in_arrows) = (false, false);
+    fn redo(&mut self) {
+        if let Some(command) = self.history.pop_redo() {
+            self.apply(&command, true);
+            self.history.push_redone(command);
+        }
+    }
 
-        for maybe_line in lines {
-            let line = maybe_line.map_err(|_| "No line here".to_owned())?;
+    /// Puts a node back in its slot
+    fn place_node(&mut self, node: Node) {
+        let id = node.id;
+        if id >= self.nodes.len() { self.nodes.resize_with(id + 1, || None); }
+        self.nodes[id] = Some(node);
+    }
 
-            if line.starts_with("Nodes = [") { in_nodes = true; continue }
-            if line.starts_with("Arrows = [") { in_arrows = true; continue; }
-            if line.starts_with("]") { in_nodes = false; in_arrows = false; continue }
+    fn place_arrow(&mut self, arrow: Arrow) {
+        let id = arrow.id;
+        if id >= self.arrows.len() { self.arrows.resize_with(id + 1, || None); }
+        self.arrows[id] = Some(arrow);
+    }
 
-            if in_nodes {
-                self.nodes.push(
-                    match line.as_str() {
-                        "none" => None,
-                        _ => Some(Node::deserialize(&line)?)
-                    }
-                );
-                continue;
-            }
-            if in_arrows {
-                self.arrows.push(
-                    match line.as_str() {
-                        "none" => None,
-                        _ => Some(Arrow::deserialize(&line)?)
-                    }
-                );
-                continue;
-            }
+    /// Inserts `label` at `index` when `present`, takes it away otherwise
+    fn put_label(&mut self, arrow: usize, index: usize, label: &str, present: bool) {
+        let Some(Some(arrow)) = self.arrows.get_mut(arrow) else { return };
+        if present {
+            arrow.labels.insert(index.min(arrow.labels.len()), label.to_owned());
+        } else {
+            arrow.remove_label_by_index(index);
         }
-        
-        Ok(())
-    }    
+    }
 
-    fn continue_computation_with_1(&mut self) -> Result<(), ()> {
-        let mut transitions = vec![];
-        let alphabet = {        
-            let mut set = std::collections::HashSet::<char>::new();
-            let mut x = Alphabet::new(self.def_blank.chars().next().unwrap_or(super::super::alphabet::DEFAULT_BLANK));
-            for maybe_arrow in &self.arrows {
-                if let Some(arrow) = maybe_arrow {
-                    for label in &arrow.labels {
-                        if label.len() != 3 { return Err(()) }
-                        let mut chars = vec![];
-                        for char in label.chars() {
-                            if char != '/' {
-                                set.insert(char);
-                                chars.push(char);
-                            }
-                        }
-                        transitions.push(
-                            (
-                                arrow.id_from_node as u8,
-                                chars[0],
-                                chars[1],
-                                arrow.id_to_node.unwrap_or(0) as u8
-                            )
-                        );
-                    }
+    /// Redoes `command` when `forward`, undoes it otherwise
+    fn apply(&mut self, command: &Command, forward: bool) {
+        match command {
+            Command::AddNode(node) => {
+                if forward { self.place_node(node.clone()) } else { clear_slot(&mut self.nodes, node.id) }
+            }
+            Command::DeleteNode(node, arrows) => {
+                if forward {
+                    clear_slot(&mut self.nodes, node.id);
+                    for arrow in arrows { clear_slot(&mut self.arrows, arrow.id); }
+                } else {
+                    self.place_node(node.clone());
+                    for arrow in arrows { self.place_arrow(arrow.clone()); }
                 }
             }
-
-            for char in set.iter() {
-                if *char != 'L' && *char != 'R' {
-                    x.add_symbol(*char).ok();
+            Command::MoveNode { id, from, to } => {
+                if let Some(Some(node)) = self.nodes.get_mut(*id) { node.top_left = if forward { *to } else { *from }; }
+            }
+            Command::SetNodeLabel { id, old, new } => {
+                if let Some(Some(node)) = self.nodes.get_mut(*id) { node.label = if forward { new } else { old }.clone(); }
+            }
+            Command::SetFinal { id, is_final, old_color } => {
+                if let Some(Some(node)) = self.nodes.get_mut(*id) {
+                    node.is_final = *is_final == forward;
+                    node.foreground_color = match (forward, node.is_final) {
+                        (false, _) => *old_color,
+                        (true, true) => FG_1,
+                        (true, false) => FG,
+                    };
                 }
             }
-            x
-        };
-        let mut m = TuringMachine::<1>::new(self.nodes.len(), alphabet.len()).map_err(|_| ())?;
-        for node in &self.nodes {
-            if let Some(node) = node {
-                if node.is_final {
-                    m.add_final_state(node.id as u8).ok();
+            Command::SetHeader { id, separate_header } => {
+                if let Some(Some(node)) = self.nodes.get_mut(*id) { node.separate_header = *separate_header == forward; }
+            }
+            Command::AddArrow(arrow) => {
+                if forward { self.place_arrow(arrow.clone()) } else { clear_slot(&mut self.arrows, arrow.id) }
+            }
+            Command::DeleteArrow(arrow) => {
+                if forward { clear_slot(&mut self.arrows, arrow.id) } else { self.place_arrow(arrow.clone()) }
+            }
+            Command::AddLabel { arrow, index, label } => self.put_label(*arrow, *index, label, forward),
+            Command::RemoveLabel { arrow, index, label } => self.put_label(*arrow, *index, label, !forward),
+            Command::EditLabel { arrow, index, old, new } => {
+                if let Some(Some(arrow)) = self.arrows.get_mut(*arrow) && let Some(label) = arrow.labels.get_mut(*index) {
+                    *label = if forward { new } else { old }.clone();
+                }
+            }
+            Command::Recolor { old, new } => {
+                for (id, color) in old {
+                    if let Some(Some(node)) = self.nodes.get_mut(*id) { node.foreground_color = if forward { *new } else { *color }; }
+                }
+            }
+            Command::Group(commands) => {
+                if forward {
+                    for command in commands { self.apply(command, true); }
+                } else {
+                    for command in commands.iter().rev() { self.apply(command, false); }
                 }
             }
+            Command::Replace { before, after, .. } => {
+                self.load_project(if forward { after } else { before }.as_ref().clone());
+            }
         }
-        let mut real_content = vec![];
-        for char in self.input.chars() {
-            real_content.push(
-                match char {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&char).unwrap_or(0),
+        self.keep_valid_selection();
+        self.dragging_arrow = None;
+        self.can_reset = true;
+    }
+
+    /// Selects `nodes`, the toolbar edits the node itself when there is only one
+    fn select_nodes(&mut self, nodes: BTreeSet<usize>) {
+        if nodes != self.selected_nodes { self.history.seal(); }
+        self.selected_node_id = if nodes.len() == 1 { nodes.first().copied() } else { None };
+        self.selected_nodes = nodes;
+        self.selected_arrow_id = None;
+    }
+
+    /// Forgets selected nodes and arrows that are gone
+    fn keep_valid_selection(&mut self) {
+        let arrow = self.selected_arrow_id.filter(|id| self.arrows.get(*id).is_some_and(|a| a.is_some()));
+        let nodes = self.selected_nodes.iter().copied().filter(|id| self.nodes.get(*id).is_some_and(|n| n.is_some())).collect();
+        self.select_nodes(nodes);
+        self.selected_arrow_id = arrow;
+    }
+
+    fn project(&self) -> Project {
+        let mut project = Project::new(&self.nodes, &self.arrows);
+        project.metadata = self.metadata.clone();
+        project.metadata.editor_version = env!("CARGO_PKG_VERSION").to_owned();
+        project.settings = Settings {
+            tapes: self.n_tapes,
+            blank: self.blank_symbol(),
+            extend_tape_on_end: self.extend_tape_on_end,
+        };
+        project.input = self.input.clone();
+        project.saved_inputs = self.saved_inputs.clone();
+        project
+    }
+
+    /// Replaces the diagram and every machine setting with the project's
+    fn load_project(&mut self, project: Project) {
+        self.discard_computation();
+        let (nodes, arrows) = project.diagram();
+        self.nodes = nodes;
+        self.arrows = arrows;
+        self.n_tapes = project.settings.tapes;
+        self.def_blank = String::from(project.settings.blank);
+        self.extend_tape_on_end = project.settings.extend_tape_on_end;
+        self.input = project.input;
+        self.saved_inputs = project.saved_inputs;
+        self.metadata = project.metadata;
+        self.select_nodes(BTreeSet::new());
+        self.dragging_arrow = None;
+        self.can_reset = true;
+    }
+
+    /// Stops the computation built for the previous machine, it may have another tape count
+    fn discard_computation(&mut self) {
+        if self.has_started { self.dispatch_stop(); }
+        self.initialized = false;
+        self.has_started = false;
+        self.is_paused = false;
+        self.can_continue = true;
+        self.outcome = None;
+        self.last_tapes.clear();
+        self.last_edits.clear();
+        self.rx = None;
+    }
+
+    /// Alphabet, machine and first tape for the diagram, labels are read with `turing::label`.
+    /// Input symbols missing from the labels are added so the tape shows them.
+    pub(crate) fn build_machine<const K: usize>(&self) -> Result<(Alphabet, TuringMachine<K>, Vec<turing::LSymbol>), String> {
+        if let Some(e) = self.blank_error() { return Err(e) }
+        let mut transitions = vec![];
+        let mut alphabet = Alphabet::new(self.blank_symbol().to_string());
+        for arrow in self.arrows.iter().flatten() {
+            for label in &arrow.labels {
+                let (read, action) = turing::label::split(label, K)?;
+                for symbol in read.iter().chain(&action) {
+                    if !turing::label::is_move(symbol) { alphabet.add_symbol(symbol.clone()).ok(); }
                 }
-            );
+                transitions.push((arrow.id_from_node as u8, read, action, arrow.id_to_node.unwrap_or(0) as u8));
+            }
         }
-        for t in transitions {
-            let (q, x, a, t) = {
-                let x = match t.1 {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.1).unwrap_or(0),
-                };
-                let y = match t.2 {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.2).unwrap_or(0),
-                };
-                (t.0, [x], [y], t.3)
-            };
+        let input = turing::label::split_input(&self.input)?;
+        for symbol in &input {
+            if !turing::label::is_move(symbol) { alphabet.add_symbol(symbol.clone()).ok(); }
+        }
+
+        let mut m = TuringMachine::<K>::new(self.nodes.len(), alphabet.len())
+            .map_err(|e| format!("The machine is too big ({:?})", e))?;
+        for node in self.nodes.iter().flatten() {
+            if node.is_final {
+                m.add_final_state(node.id as u8).ok();
+            }
+        }
+        let l_symbol = |symbol: &str| match symbol {
+            turing::label::LEFT => m.sx(),
+            turing::label::RIGHT => m.dx(),
+            _ => alphabet.get_l_symbol(symbol).unwrap_or(0),
+        };
+        let real_content: Vec<turing::LSymbol> = input.iter().map(|symbol| l_symbol(symbol)).collect();
+        let transitions: Vec<_> = transitions.into_iter().map(|(q, read, action, t)| {
+            let (mut x, mut a) = ([0; K], [0; K]);
+            for i in 0..K {
+                x[i] = l_symbol(&read[i]);
+                a[i] = l_symbol(&action[i]);
+            }
+            (q, x, a, t)
+        }).collect();
+        for (q, x, a, t) in transitions {
             m.add_transition(q, x, a, t).ok();
         }
+        Ok((alphabet, m, real_content))
+    }
 
+    fn continue_computation_with_1(&mut self) -> Result<(), String> {
+        let (alphabet, m, real_content) = self.build_machine::<1>()?;
         if let Ok(mut mutex) = self.computations.0.lock() {
             self.popup_string = Some("Test".to_owned());
             mutex.reset();
@@ -365,91 +1019,12 @@ impl NodeEditor {
             mutex.use_tapes([Tape::with_content(real_content, self.extend_tape_on_end)]);
         }
         self.initialized = true;
-        self.last_tape = Some(self.input.clone());
+        self.last_tapes.clear();
         Ok(())
     }
 
-    fn continue_computation_with_2(&mut self) -> Result<(), ()> {
-        let mut transitions = vec![];
-        let alphabet = {        
-            let mut set = std::collections::HashSet::<char>::new();
-            let mut x = Alphabet::new(self.def_blank.chars().next().unwrap_or(super::super::alphabet::DEFAULT_BLANK));
-            for maybe_arrow in &self.arrows {
-                if let Some(arrow) = maybe_arrow {
-                    for label in &arrow.labels {
-                        if label.len() != 5 { return Err(()) }
-                        let mut chars = vec![];
-                        for char in label.chars() {
-                            if char != '/' {
-                                set.insert(char);
-                                chars.push(char);
-                            }
-                        }
-                        transitions.push(
-                            (
-                                arrow.id_from_node as u8,
-                                [chars[0], chars[1]],
-                                [chars[2], chars[3]],
-                                arrow.id_to_node.unwrap_or(0) as u8
-                            )
-                        );
-                    }
-                }
-            }
-
-            for char in set.iter() {
-                if *char != 'L' && *char != 'R' {
-                    x.add_symbol(*char).ok();
-                }
-            }
-            x
-        };
-        let mut m = TuringMachine::<2>::new(self.nodes.len(), alphabet.len()).map_err(|_| ())?;
-        for node in &self.nodes {
-            if let Some(node) = node {
-                if node.is_final {
-                    m.add_final_state(node.id as u8).ok();
-                }
-            }
-        }
-        let mut real_content = vec![];
-        for char in self.input.chars() {
-            real_content.push(
-                match char {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&char).unwrap_or(0),
-                }
-            );
-        }
-        for t in transitions {
-            let (q, x, a, t) = {
-                let x1 = match t.1[0] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.1[0]).unwrap_or(0),
-                };
-                let x2 = match t.1[1] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.1[1]).unwrap_or(0),
-                };
-
-                let y1 = match t.2[0] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.2[0]).unwrap_or(0),
-                };
-                let y2 = match t.2[1] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.2[1]).unwrap_or(0),
-                };
-                (t.0, [x1, x2], [y1, y2], t.3)
-            };
-            m.add_transition(q, x, a, t).ok();
-        }
-
+    fn continue_computation_with_2(&mut self) -> Result<(), String> {
+        let (alphabet, m, real_content) = self.build_machine::<2>()?;
         if let Ok(mut mutex) = self.computations.1.lock() {
             self.popup_string = Some("Test".to_owned());
             mutex.reset();
@@ -459,100 +1034,11 @@ impl NodeEditor {
             mutex.use_tapes([Tape::with_content(real_content, self.extend_tape_on_end), Tape::with_size(len, self.extend_tape_on_end)]);
         }
         self.initialized = true;
-        self.last_tape = Some(self.input.clone());
+        self.last_tapes.clear();
         Ok(())
     }
-    fn continue_computation_with_3(&mut self) -> Result<(), ()> {
-        let mut transitions = vec![];
-        let alphabet = {        
-            let mut set = std::collections::HashSet::<char>::new();
-            let mut x = Alphabet::new(self.def_blank.chars().next().unwrap_or(super::super::alphabet::DEFAULT_BLANK));
-            for maybe_arrow in &self.arrows {
-                if let Some(arrow) = maybe_arrow {
-                    for label in &arrow.labels {
-                        if label.len() != 7 { return Err(()) }
-                        let mut chars = vec![];
-                        for char in label.chars() {
-                            if char != '/' {
-                                set.insert(char);
-                                chars.push(char);
-                            }
-                        }
-                        transitions.push(
-                            (
-                                arrow.id_from_node as u8,
-                                [chars[0], chars[1], chars[2]],
-                                [chars[3], chars[4], chars[5]],
-                                arrow.id_to_node.unwrap_or(0) as u8
-                            )
-                        );
-                    }
-                }
-            }
-
-            for char in set.iter() {
-                if *char != 'L' && *char != 'R' {
-                    x.add_symbol(*char).ok();
-                }
-            }
-            x
-        };
-        let mut m = TuringMachine::<3>::new(self.nodes.len(), alphabet.len()).map_err(|_| ())?;
-        for node in &self.nodes {
-            if let Some(node) = node {
-                if node.is_final {
-                    m.add_final_state(node.id as u8).ok();
-                }
-            }
-        }
-        let mut real_content = vec![];
-        for char in self.input.chars() {
-            real_content.push(
-                match char {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&char).unwrap_or(0),
-                }
-            );
-        }
-        for t in transitions {
-            let (q, x, a, t) = {
-                let x1 = match t.1[0] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.1[0]).unwrap_or(0),
-                };
-                let x2 = match t.1[1] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.1[1]).unwrap_or(0),
-                };
-                let x3 = match t.1[2] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.1[2]).unwrap_or(0),
-                };
-
-                let y1 = match t.2[0] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.2[0]).unwrap_or(0),
-                };
-                let y2 = match t.2[1] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.2[1]).unwrap_or(0),
-                };
-                let y3 = match t.2[2] {
-                    'L' => m.sx(),
-                    'R' => m.dx(),
-                    _ => alphabet.get_l_symbol(&t.2[2]).unwrap_or(0),
-                };
-                (t.0, [x1, x2, x3], [y1, y2, y3], t.3)
-            };
-            m.add_transition(q, x, a, t).ok();
-        }
-
+    fn continue_computation_with_3(&mut self) -> Result<(), String> {
+        let (alphabet, m, real_content) = self.build_machine::<3>()?;
         if let Ok(mut mutex) = self.computations.2.lock() {
             self.popup_string = Some("Test".to_owned());
             mutex.reset();
@@ -562,11 +1048,11 @@ impl NodeEditor {
             mutex.use_tapes([Tape::with_content(real_content, self.extend_tape_on_end), Tape::with_size(len, self.extend_tape_on_end), Tape::with_size(len, self.extend_tape_on_end)]);
         }
         self.initialized = true;
-        self.last_tape = Some(self.input.clone());
+        self.last_tapes.clear();
         Ok(())
     }
 
-    fn initialize_computation(&mut self) -> Result<(), ()> {
+    fn initialize_computation(&mut self) -> Result<(), String> {
         match self.n_tapes {
             1 => self.continue_computation_with_1(),
             2 => self.continue_computation_with_2(),
@@ -576,35 +1062,6 @@ impl NodeEditor {
     }
 }
 
-impl super::Serializable for NodeEditor {
-    fn serialize(&self) -> String {
-        let mut result = String::from("Nodes = [\n");
-        for maybe_node in &self.nodes {
-            result.push_str(format!("{}\n",
-                match maybe_node {
-                    None => String::from("none"),
-                    Some(node) => node.serialize(),
-                }
-            ).as_str());
-        }
-        result.push_str("]\nArrows = [\n");
-        for maybe_arrow in &self.arrows {
-            result.push_str(
-                format!("{}\n",
-                    match maybe_arrow {
-                        None => String::from("none"),
-                        Some(arrow) => arrow.serialize()
-                    }
-                ).as_str()
-            );
-        }
-        result.push(']');
-        result
-    }
-}
-
-
-
 impl eframe::App for NodeEditor {
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
@@ -616,8 +1073,59 @@ impl eframe::App for NodeEditor {
             }
         }
 
-        if let Some((i, j)) = self.to_remove_next_frame.take() { self.arrows[i].as_mut().unwrap().remove_label_by_index(j);}
+        if let Some((i, j)) = self.to_remove_next_frame.take() {
+            let arrow = self.arrows[i].as_mut().unwrap();
+            if j < arrow.labels.len() {
+                let label = arrow.labels.remove(j);
+                self.history.record(Command::RemoveLabel { arrow: i, index: j, label });
+            }
+        }
+
+        // Text fields keep their own undo
+        if !ctx.wants_keyboard_input() {
+            let redo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z);
+            let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+            if ctx.input_mut(|i| i.consume_shortcut(&redo)) { self.redo(); }
+            else if ctx.input_mut(|i| i.consume_shortcut(&undo)) { self.undo(); }
+            let duplicate = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::D);
+            if ctx.input_mut(|i| i.consume_shortcut(&duplicate)) { self.duplicate_selection(); }
+            // The integration turns the clipboard shortcuts into events
+            let events = ctx.input(|i| i.events.clone());
+            for event in events {
+                match event {
+                    egui::Event::Copy => self.copy_selection(ctx),
+                    egui::Event::Cut => self.cut_selection(ctx),
+                    egui::Event::Paste(text) => self.paste(&text),
+                    _ => {}
+                }
+            }
+        }
         if let Some(_) = self.clear_outcome { self.outcome.take(); }
+        if !self.initialized {
+            self.current_state = None;
+            self.fired = None;
+            self.trail.clear();
+        } else if let Some(progress) = self.dispatch_progress() {
+            if progress.transition_count != self.transition_count {
+                self.transition_count = progress.transition_count;
+                self.fired_at = ctx.input(|i| i.time);
+            }
+            self.current_state = Some(progress.current as usize);
+            self.fired = progress.last.map(|(q, read, t)| (q as usize, read, t as usize));
+            self.trail = progress.recent.into_iter().map(usize::from).collect();
+        }
+        // A running machine moves on by itself and flashes fade without input
+        let running = self.initialized && self.has_started && self.can_continue && !self.is_paused;
+        if running || (self.fired.is_some() && ctx.input(|i| i.time) - self.fired_at < FLASH_SECONDS) {
+            ctx.request_repaint();
+        }
+
+        if self.text_view {
+            egui::SidePanel::right("text-view")
+                .resizable(true)
+                .default_width(450.0)
+                .show(ctx, |ui| self.show_text_view(ui));
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             let (size_x, size_y) = {
@@ -650,21 +1158,29 @@ impl eframe::App for NodeEditor {
                                         egui::RichText::new("Delete Node").font(egui::FontId::monospace(20.0))
                                     )).clicked() {
                                         self.delete_node(node_id);
-                                        self.selected_node_id = None;
+                                        self.select_nodes(BTreeSet::new());
                                     }
                                     if let Some(node) = &mut self.nodes[node_id] {
                                         ui.group(|ui| {
                                             ui.label(egui::RichText::new("Label: ").font(egui::FontId::monospace(20.0)));
-                                            ui.add(
+                                            let old = node.label.clone();
+                                            if ui.add(
                                                 egui::TextEdit::singleline(&mut node.label)
                                                     .font(egui::FontId::monospace(20.0))
                                                     .background_color(Color32::TRANSPARENT)
                                                     .desired_width(100.0)
-                                            );
-                                            ui.checkbox(&mut node.separate_header, 
+                                            ).lost_focus() {
+                                                self.history.seal();
+                                            }
+                                            if ui.checkbox(&mut node.separate_header, 
                                                 egui::RichText::new("Show header").font(egui::FontId::monospace(20.0))
-                                            );
-                                            if node.label.len() > 10 { node.label.truncate(10) }
+                                            ).changed() {
+                                                self.history.record(Command::SetHeader { id: node_id, separate_header: node.separate_header });
+                                            }
+                                            if node.label.chars().count() > 10 { node.label = node.label.chars().take(10).collect(); }
+                                            if node.label != old {
+                                                self.history.record(Command::SetNodeLabel { id: node_id, old, new: node.label.clone() });
+                                            }
 
                                             ui.add_space(10.0);
 
@@ -673,12 +1189,50 @@ impl eframe::App for NodeEditor {
                                             );
                                             if response.changed() {
                                                 self.can_reset = true;
+                                                self.history.record(Command::SetFinal { id: node_id, is_final: node.is_final, old_color: node.foreground_color });
+                                                if node.is_final { node.foreground_color =  FG_1 }
+                                                else { node.foreground_color = FG }
                                             }
-                                            if node.is_final { node.foreground_color =  FG_1 }
-                                            else { node.foreground_color = FG }
                                         });
                                     }
                                     ui.add_space(10.0);
+                                } else if self.selected_nodes.len() > 1 {
+                                    let ids: Vec<usize> = self.selected_nodes.iter().copied().collect();
+                                    ui.group(|ui| {
+                                        ui.label(egui::RichText::new(format!("{} nodes selected", ids.len())).font(egui::FontId::monospace(20.0)));
+                                    });
+                                    if ui.add_sized([120.0, 40.0], egui::Button::new(
+                                        egui::RichText::new("Delete Nodes").font(egui::FontId::monospace(20.0))
+                                    )).clicked() {
+                                        self.delete_nodes(&ids);
+                                        self.select_nodes(BTreeSet::new());
+                                    }
+                                    if ui.add_sized([120.0, 40.0], egui::Button::new(
+                                        egui::RichText::new("Toggle Final").font(egui::FontId::monospace(20.0))
+                                    )).clicked() {
+                                        self.toggle_final(&ids);
+                                    }
+                                    if ui.add_sized([80.0, 40.0], egui::Button::new(
+                                        egui::RichText::new("Copy").font(egui::FontId::monospace(20.0))
+                                    )).on_hover_text("Ctrl+C, paste with Ctrl+V").clicked() {
+                                        self.copy_selection(ui.ctx());
+                                    }
+                                    if ui.add_sized([80.0, 40.0], egui::Button::new(
+                                        egui::RichText::new("Cut").font(egui::FontId::monospace(20.0))
+                                    )).on_hover_text("Ctrl+X").clicked() {
+                                        self.cut_selection(ui.ctx());
+                                    }
+                                    if ui.add_sized([120.0, 40.0], egui::Button::new(
+                                        egui::RichText::new("Duplicate").font(egui::FontId::monospace(20.0))
+                                    )).on_hover_text("Ctrl+D").clicked() {
+                                        self.duplicate_selection();
+                                    }
+                                    ui.group(|ui| {
+                                        ui.label(egui::RichText::new("Color: ").font(egui::FontId::monospace(20.0)));
+                                        if egui::color_picker::color_edit_button_srgba(ui, &mut self.group_color, egui::color_picker::Alpha::Opaque).changed() {
+                                            self.recolor(&ids, self.group_color);
+                                        }
+                                    });
                                 } else if let Some(arrow_id) = self.selected_arrow_id {
                                     ui.group(|ui| {
                                         ui.label(egui::RichText::new(format!("Selected Arrow: {}", arrow_id)).font(egui::FontId::monospace(20.0)));
@@ -700,6 +1254,7 @@ impl eframe::App for NodeEditor {
                                             );
                                             if ui.button(egui::RichText::new("Add label").font(egui::FontId::monospace(20.0))).clicked() {
                                                 let x = std::mem::take(&mut self.new_label);
+                                                self.history.record(Command::AddLabel { arrow: arrow_id, index: arrow.labels.len(), label: x.clone() });
                                                 arrow.add_label(x);
                                             }
                                         }
@@ -708,35 +1263,176 @@ impl eframe::App for NodeEditor {
                                     if ui.add_sized([120.0, 40.0], egui::Button::new(
                                         egui::RichText::new("New Node").font(egui::FontId::monospace(20.0))
                                     )).clicked() {
-                                        let node = make_node(0, true);
-                                        self.insert_new_node(node);
+                                        let mut node = make_node(0, true);
+                                        node.top_left = self.new_node_position();
+                                        let id = self.insert_new_node(node);
+                                        self.history.record(Command::AddNode(self.nodes[id].clone().unwrap()));
+                                    }
+                                    ui.menu_button(egui::RichText::new("Insert from library").font(egui::FontId::monospace(20.0)), |ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.label(egui::RichText::new("Symbols").font(egui::FontId::monospace(15.0)));
+                                            ui.add(
+                                                egui::TextEdit::singleline(&mut self.library_symbols)
+                                                    .font(egui::FontId::monospace(15.0))
+                                                    .desired_width(100.0)
+                                            );
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label(egui::RichText::new("Marker").font(egui::FontId::monospace(15.0)));
+                                            ui.add(
+                                                egui::TextEdit::singleline(&mut self.library_marker)
+                                                    .font(egui::FontId::monospace(15.0))
+                                                    .desired_width(20.0)
+                                            );
+                                        });
+                                        if self.library_marker.chars().count() > 1 { self.library_marker = self.library_marker.chars().take(1).collect(); }
+                                        ui.separator();
+                                        for kind in BlockKind::ALL {
+                                            if ui.button(egui::RichText::new(kind.name()).font(egui::FontId::monospace(15.0)))
+                                                .on_hover_text(kind.description())
+                                                .clicked()
+                                            {
+                                                self.insert_from_library(kind);
+                                                ui.close();
+                                            }
+                                        }
+                                    });
+                                    ui.menu_button(egui::RichText::new("Layout").font(egui::FontId::monospace(20.0)), |ui| {
+                                        for kind in LayoutKind::ALL {
+                                            if ui.button(egui::RichText::new(kind.name()).font(egui::FontId::monospace(15.0)))
+                                                .on_hover_text(kind.description())
+                                                .clicked()
+                                            {
+                                                self.auto_layout(kind);
+                                                ui.close();
+                                            }
+                                        }
+                                    }).response.on_hover_text("Applies to the selected nodes, or to all of them");
+                                    ui.menu_button(egui::RichText::new("Arrange").font(egui::FontId::monospace(20.0)), |ui| {
+                                        ui.checkbox(&mut self.show_grid, egui::RichText::new("Show grid").font(egui::FontId::monospace(15.0)));
+                                        ui.checkbox(&mut self.snap_to_grid, egui::RichText::new("Snap to grid").font(egui::FontId::monospace(15.0)));
+                                        ui.checkbox(&mut self.show_guides, egui::RichText::new("Alignment guides").font(egui::FontId::monospace(15.0)));
+                                        ui.separator();
+                                        let selected = self.selected_nodes.len();
+                                        for alignment in Alignment::ALL {
+                                            if ui.add_enabled(selected > 1, egui::Button::new(egui::RichText::new(alignment.name()).font(egui::FontId::monospace(15.0)))).clicked() {
+                                                self.arrange_selection(|rects| arrange::align(rects, alignment));
+                                                ui.close();
+                                            }
+                                        }
+                                        ui.separator();
+                                        for (name, vertical) in [("Distribute horizontally", false), ("Distribute vertically", true)] {
+                                            if ui.add_enabled(selected > 2, egui::Button::new(egui::RichText::new(name).font(egui::FontId::monospace(15.0)))).clicked() {
+                                                self.arrange_selection(|rects| arrange::distribute(rects, vertical));
+                                                ui.close();
+                                            }
+                                        }
+                                    });
+                                    if ui.add_sized([120.0, 40.0], egui::Button::new(
+                                        egui::RichText::new("Fit all").font(egui::FontId::monospace(20.0))
+                                    )).clicked() {
+                                        self.fit_all();
                                     }
                                     if ui.add_sized([120.0, 40.0], egui::Button::new(
                                         egui::RichText::new("Save").font(egui::FontId::monospace(20.0))
                                     )).clicked() {
-                                        let export = self.serialize();
-                                        if let Some(path) = rfd::FileDialog::new()
-                                            .set_title("Save")
-                                            .set_file_name("export.txt")
-                                            .add_filter("Text", &["txt"])
-                                            .save_file()
-                                        {
-                                            std::fs::write(path,  export).ok();
-                                        }
+                                        let export = self.project().to_json();
+                                        self.save_export("Project", "machine.json", &["json"], export);
                                     }
                                     if ui.add_sized([120.0, 40.0], egui::Button::new(
                                         egui::RichText::new("Load").font(egui::FontId::monospace(20.0))
                                     )).clicked() {
                                         if let Some(path) = rfd::FileDialog::new()
                                             .set_title("Load")
-                                            .add_filter("Text", &["txt"])
+                                            .add_filter("Project", &["json", "txt"])
                                             .pick_file()
                                         {
-                                            if let Ok(file) = std::fs::File::open(path) {
-                                                self.from_serialized(file).ok();
-                                            } else { self.popup_string = Some("Could not open file".to_owned()); }
+                                            match std::fs::read_to_string(path) {
+                                                // Nothing changes until the whole file is read and checked
+                                                Ok(source) => match Project::parse(&source) {
+                                                    Ok(project) => {
+                                                        let problems = project.problems();
+                                                        if problems.is_empty() {
+                                                            self.replace_with(|editor| editor.load_project(project), false);
+                                                        } else {
+                                                            self.pending_repair = Some((project, problems));
+                                                        }
+                                                    }
+                                                    Err(e) => self.popup_string = Some(format!("Could not load the project\n{}", e)),
+                                                },
+                                                Err(_) => self.popup_string = Some("Could not open file".to_owned()),
+                                            }
                                         }
                                     }
+                                    ui.menu_button(egui::RichText::new("Import").font(egui::FontId::monospace(20.0)), |ui| {
+                                        if ui.button(egui::RichText::new("Machine text (.tm)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.import_with("Machine text", &["tm", "txt"], |s| formats::text::parse(s).map(|d| (d, vec![])));
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("JFLAP (.jff)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.import_with("JFLAP", &["jff"], formats::jflap::parse);
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("turingmachine.io (.yaml)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.import_with("turingmachine.io", &["yaml", "yml"], formats::yaml::parse);
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("Morphett program (.txt)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            // Wildcards also have to cover what the current input uses
+                                            let input: Vec<char> = turing::label::split_input(&self.input).unwrap_or_default().iter()
+                                                .filter_map(|s| if s.chars().count() == 1 { s.chars().next() } else { None })
+                                                .collect();
+                                            self.import_with("Morphett program", &["txt"], |s| formats::morphett::parse(s, &input));
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("Busy beaver notation (.txt)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.import_with("Busy beaver notation", &["txt"], formats::busy_beaver::parse);
+                                            ui.close();
+                                        }
+                                    });
+                                    ui.menu_button(egui::RichText::new("Export").font(egui::FontId::monospace(20.0)), |ui| {
+                                        if ui.button(egui::RichText::new("Machine text (.tm)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.export_with("Machine text", "machine.tm", &["tm"], |d| Ok(formats::text::print(d)));
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("JFLAP (.jff)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.export_with("JFLAP", "machine.jff", &["jff"], formats::jflap::print);
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("turingmachine.io (.yaml)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.export_with("turingmachine.io", "machine.yaml", &["yaml", "yml"], formats::yaml::print);
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("Busy beaver notation (.txt)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.export_with("Busy beaver notation", "machine.txt", &["txt"], formats::busy_beaver::print);
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("Graphviz DOT (.dot)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.export_with("Graphviz DOT", "machine.dot", &["dot", "gv"], |d| Ok(formats::dot::print(d, None)));
+                                            ui.close();
+                                        }
+                                        if self.initialized && ui.button(egui::RichText::new("Graphviz DOT with the run (.dot)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            match self.last_run() {
+                                                Some(run) => self.export_with("Graphviz DOT", "run.dot", &["dot", "gv"], |d| Ok(formats::dot::print(d, Some(&run)))),
+                                                None => self.popup_string = Some("The computation is busy, pause it to export the run".to_owned()),
+                                            }
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("TikZ picture (.tex)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.export_with("TikZ picture", "machine.tex", &["tex"], |d| Ok(formats::tikz::picture(d)));
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("LaTeX transition table (.tex)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            self.export_with("LaTeX transition table", "table.tex", &["tex"], formats::tikz::table);
+                                            ui.close();
+                                        }
+                                        if ui.button(egui::RichText::new("SVG picture (.svg)").font(egui::FontId::monospace(15.0))).clicked() {
+                                            let picture = formats::svg::print(&self.nodes, &self.arrows);
+                                            self.save_export("SVG picture", "machine.svg", &["svg"], Ok(picture));
+                                            ui.close();
+                                        }
+                                    });
+                                    ui.checkbox(&mut self.text_view, egui::RichText::new("Text view").font(egui::FontId::monospace(20.0)));
                                     if !self.has_started {
                                         ui.group(|ui| {
                                             ui.vertical(|ui| {
@@ -761,6 +1457,9 @@ impl eframe::App for NodeEditor {
                                                             .desired_width(20.0)
                                                         );
                                                     });
+                                                    if let Some(e) = self.blank_error() {
+                                                        ui.label(egui::RichText::new(e).font(egui::FontId::monospace(15.0)).color(FG_1));
+                                                    }
                                                     ui.separator();
                                                     ui.horizontal(|ui| {
                                                         ui.checkbox(
@@ -769,7 +1468,6 @@ impl eframe::App for NodeEditor {
                                                         );
                                                     });
                                                 });
-                                                if self.def_blank.len() > 1 { self.def_blank.truncate(1); }
                                             });
                                         });
                                     }
@@ -778,8 +1476,8 @@ impl eframe::App for NodeEditor {
                                     )).clicked() {
                                         if self.can_reset {
                                             match self.initialize_computation() {
-                                                Err(_) => {
-                                                    self.popup_string = Some(String::from("Could not initialize"));
+                                                Err(e) => {
+                                                    self.popup_string = Some(format!("Could not initialize\n{}", e));
                                                 }
                                                 Ok(_) => {
                                                     self.popup_string = Some(String::from("Initialization successful"));
@@ -869,6 +1567,9 @@ impl eframe::App for NodeEditor {
                                             }
                                         }                                            
                                         ui.checkbox(&mut self.show_execution, egui::RichText::new("Show execution").font(egui::FontId::monospace(20.0)));
+                                        ui.label(egui::RichText::new("Trail").font(egui::FontId::monospace(20.0)));
+                                        ui.add(egui::DragValue::new(&mut self.trail_length).range(0..=turing::computation::RECENT_LIMIT))
+                                            .on_hover_text("How many of the last visited states stay highlighted");
                                     }
                                 }
                                 if let Some((is_on_final, terminated, output)) = &self.outcome {
@@ -902,23 +1603,32 @@ impl eframe::App for NodeEditor {
                             egui::Window::new("Real time tape content")
                                 .collapsible(false)
                                 .resizable(true)
+                                .default_width(600.0)
                                 .show(ui.ctx(), |ui| {
-                                    egui::ScrollArea::horizontal().id_salt(5).show(ui, |ui| {
-
-                                        let mut to_show: Option<String> = {
-                                            self.dispatch_only_output()
-                                        };
-                                        if let Some(_) = to_show {
-                                            // update last tape
-                                            self.last_tape = to_show.take();
+                                    if let Some(tapes) = self.dispatch_tapes() { self.last_tapes = tapes; }
+                                    if self.last_tapes.is_empty() {
+                                        ui.label(egui::RichText::new("Could not get tape info").font(egui::FontId::monospace(20.0)));
+                                    } else {
+                                        // The running thread only waits between steps while paused, and
+                                        // starting moves every head back to cell 0
+                                        let editable = self.initialized && self.can_continue && self.has_started && self.is_paused;
+                                        let symbols = if editable { self.dispatch_symbols().unwrap_or_default() } else { vec![] };
+                                        let last_tapes = std::mem::take(&mut self.last_tapes);
+                                        if let Some(edit) = self.tape_view.show(ui, &last_tapes, &symbols, editable) {
+                                            if let Err(e) = self.dispatch_edit_tape(edit) { self.popup_string = Some(e); }
+                                            self.can_reset = true;
                                         }
-
-                                        ui.label(
-                                            egui::RichText::new(
-                                                self.last_tape.as_ref().unwrap_or(&"Could not get tape info".to_owned())
-                                            ).font(egui::FontId::monospace(20.0))
-                                        );
-                                    });
+                                        self.last_tapes = last_tapes;
+                                        if let Some(edits) = self.dispatch_manual_edits() { self.last_edits = edits; }
+                                        if !self.last_edits.is_empty() {
+                                            egui::CollapsingHeader::new(egui::RichText::new(format!("Edited by hand ({})", self.last_edits.len())).font(egui::FontId::monospace(15.0)))
+                                                .show(ui, |ui| {
+                                                    for edit in &self.last_edits {
+                                                        ui.label(egui::RichText::new(edit.to_string()).font(egui::FontId::monospace(12.0)));
+                                                    }
+                                                });
+                                        }
+                                    }
                                 });
                         }
                         if let Some(msg_str) = self.popup_string.take() {
@@ -934,56 +1644,167 @@ impl eframe::App for NodeEditor {
                                     });
                                 });
                         }
+                        if let Some((_, problems)) = &self.pending_repair {
+                            let mut choice = None;
+                            egui::Window::new("Repair project")
+                                .collapsible(false)
+                                .resizable(false)
+                                .show(ui.ctx(), |ui| {
+                                    ui.label(egui::RichText::new("The project has problems:").font(egui::FontId::monospace(20.0)));
+                                    egui::ScrollArea::vertical().id_salt("repair-problems").max_height(300.0).show(ui, |ui| {
+                                        for problem in problems {
+                                            ui.label(egui::RichText::new(problem).font(egui::FontId::monospace(15.0)));
+                                        }
+                                    });
+                                    ui.separator();
+                                    ui.horizontal(|ui| {
+                                        if ui.button(egui::RichText::new("Repair and load").font(egui::FontId::monospace(20.0)))
+                                            .on_hover_text("Drops arrows pointing at missing nodes, merges duplicated arrows and renumbers ids")
+                                            .clicked()
+                                        {
+                                            choice = Some(true);
+                                        }
+                                        if ui.button(egui::RichText::new("Cancel").font(egui::FontId::monospace(20.0))).clicked() {
+                                            choice = Some(false);
+                                        }
+                                    });
+                                });
+                            if let Some(repair) = choice
+                                && let Some((mut project, _)) = self.pending_repair.take()
+                                && repair
+                            {
+                                project.repair();
+                                self.replace_with(|editor| editor.load_project(project), false);
+                            }
+                        }
                         ui.group( |ui| {
                             ui.set_min_size(egui::vec2(size.x * 0.99, y_2));
                             let group_rect = Rect::from_min_max(Pos2::new(25.0 ,y_1 + 45.0), Pos2::new(size.x * 0.99 + 15.0, y_1 + 40.0 + y_2));
-                            let response = ui.interact(group_rect, egui::Id::new("sandbox-bg"), egui::Sense::click());
+                            self.canvas_rect = group_rect;
+                            let painter = ui.painter().with_clip_rect(group_rect);
+                            let response = ui.interact(group_rect, egui::Id::new("sandbox-bg"), egui::Sense::click_and_drag());
+                            // Nodes cover the background, the wheel zooms over them too
+                            if ui.rect_contains_pointer(group_rect) {
+                                let (scroll, pinch, pointer) = ui.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta(), i.pointer.hover_pos()));
+                                let factor = pinch * (scroll / 200.0).exp();
+                                if let Some(pointer) = pointer && factor != 1.0 { self.viewport.zoom_at(pointer, factor); }
+                            }
+                            let mut pan = egui::Vec2::ZERO;
+                            if response.dragged_by(egui::PointerButton::Middle) { pan += response.drag_delta(); }
+                            let transform = self.viewport.transform;
+                            if self.show_grid { arrange::draw_grid(&painter, group_rect, transform); }
+                            let shift = ui.input(|i| i.modifiers.shift);
                             if response.clicked() {
-                                self.selected_node_id = None;
-                                self.selected_arrow_id = None;
+                                if !shift { self.select_nodes(BTreeSet::new()); }
                                 if let Some(pos) = response.interact_pointer_pos() {
+                                    let pos = self.viewport.to_world(pos);
                                     for maybe_arrow in &self.arrows {
                                         if let Some(arrow) = maybe_arrow {
-                                            if arrow.is_near_curve(pos) {
+                                            if arrow.is_near_curve(pos, ARROW_HIT_DISTANCE / transform.scaling) {
+                                                self.selected_node_id = None;
+                                                self.selected_nodes.clear();
                                                 self.selected_arrow_id = Some(arrow.id);
                                             }
                                         }
                                     }
                                 }
                             }
+                            if response.drag_started_by(egui::PointerButton::Primary) {
+                                self.band_start = ui.input(|i| i.pointer.press_origin()).map(|p| self.viewport.to_world(p));
+                            }
+
+                            // The current state in full, older states of the trail fainter
+                            let mut highlight = HashMap::<usize, f32>::new();
+                            let trail = &self.trail[self.trail.len().saturating_sub(self.trail_length)..];
+                            for (age, q) in trail.iter().rev().enumerate() {
+                                highlight.entry(*q).or_insert(1.0 - (age + 1) as f32 / (self.trail_length + 1) as f32);
+                            }
+                            if let Some(q) = self.current_state { highlight.insert(q, 1.0); }
+
+                            let mut dragged = None;
+                            let mut finished_drag = None;
+                            let mut clicked_node = None;
 
                             for maybe_node in &mut self.nodes {
                                 if let Some(node) = maybe_node {
-                                    let response = ui.interact(node.rect(), egui::Id::new(node.id), egui::Sense::click_and_drag());
+                                    // Nodes out of the canvas must not take clicks from the rest of the window
+                                    let response = ui.interact((transform * node.rect()).intersect(group_rect), egui::Id::new(node.id), egui::Sense::click_and_drag());
+                                    if response.drag_started_by(egui::PointerButton::Primary) {
+                                        self.drag_start = Some(node.top_left);
+                                        self.drag_raw = node.top_left;
+                                        self.drag_group = if self.selected_nodes.contains(&node.id) {
+                                            self.selected_nodes.iter().copied().filter(|id| *id != node.id).collect()
+                                        } else {
+                                            vec![]
+                                        };
+                                    }
                                     if response.dragged_by(egui::PointerButton::Primary) {
-                                        node.change_position(response.drag_delta());
-
-                                        let x_clamped = node.top_left.x.clamp(
-                                            group_rect.min.x, group_rect.max.x - node.size.x
-                                        );
-                                        let y_clamped = node.top_left.y.clamp(
-                                            group_rect.min.y, group_rect.max.y - node.size.y
-                                        );
-                                        node.top_left = Pos2::new(x_clamped, y_clamped);
+                                        dragged = Some((node.id, response.drag_delta() / transform.scaling));
+                                    }
+                                    if response.dragged_by(egui::PointerButton::Middle) { pan += response.drag_delta(); }
+                                    if response.drag_stopped_by(egui::PointerButton::Primary)
+                                        && let Some(from) = self.drag_start.take()
+                                        && from != node.top_left
+                                    {
+                                        finished_drag = Some((node.id, from, node.top_left));
                                     }
                                     if response.clicked_by(egui::PointerButton::Secondary) {
                                         let pos = node.get_output_edge();
                                         self.dragging_arrow = Some(make_arrow(self.arrows.len(), pos, pos, node.id));
                                     }
                                     if response.clicked_by(egui::PointerButton::Primary) {
-                                        self.selected_node_id = Some(node.id);
-                                        self.selected_arrow_id = None;
+                                        clicked_node = Some(node.id);
+                                    }
+                                    node.draw(&painter, transform);
+                                    if let Some(strength) = highlight.get(&node.id) {
+                                        let stroke = egui::Stroke::new(4.0 * transform.scaling, CURRENT_STATE.gamma_multiply(*strength));
+                                        painter.rect_stroke((transform * node.rect()).expand(2.0), 10.0 * transform.scaling, stroke, egui::StrokeKind::Outside);
+                                    }
+                                    if self.selected_nodes.contains(&node.id) {
+                                        painter.rect_stroke(transform * node.rect(), 10.0 * transform.scaling, egui::Stroke::new(2.0, SELECTION), egui::StrokeKind::Outside);
                                     }
-                                    node.draw(ui.painter());
                                 }
                             }
+                            if let Some((id, delta)) = dragged {
+                                self.drag_raw += delta;
+                                let others: Vec<Rect> = self.nodes.iter().flatten()
+                                    .filter(|n| n.id != id && !self.drag_group.contains(&n.id))
+                                    .map(|n| n.rect())
+                                    .collect();
+                                let (target, guides) = arrange::snap(
+                                    Rect::from_min_size(self.drag_raw, NODE_SIZE), &others,
+                                    self.snap_to_grid, self.show_guides, GUIDE_TOLERANCE / transform.scaling
+                                );
+                                let mut group_delta = egui::Vec2::ZERO;
+                                if let Some(Some(node)) = self.nodes.get_mut(id) {
+                                    group_delta = target - node.top_left;
+                                    node.top_left = target;
+                                }
+                                for other in &self.drag_group {
+                                    if let Some(Some(node)) = self.nodes.get_mut(*other) { node.change_position(group_delta); }
+                                }
+                                arrange::draw_guides(&painter, group_rect, transform, &guides);
+                            }
+                            if let Some((id, from, to)) = finished_drag {
+                                let mut moves = vec![Command::MoveNode { id, from, to }];
+                                for other in std::mem::take(&mut self.drag_group) {
+                                    if let Some(Some(node)) = self.nodes.get(other) {
+                                        moves.push(Command::MoveNode { id: other, from: node.top_left - (to - from), to: node.top_left });
+                                    }
+                                }
+                                self.history.record(if moves.len() == 1 { moves.remove(0) } else { Command::Group(moves) });
+                            }
+                            if let Some(id) = clicked_node {
+                                let mut nodes = if shift { self.selected_nodes.clone() } else { BTreeSet::new() };
+                                if !nodes.remove(&id) { nodes.insert(id); }
+                                self.select_nodes(nodes);
+                            }
                                                     
                             if let Some(arrow) = &mut self.dragging_arrow {
                                 if let Some(mouse_position) = ui.ctx().pointer_hover_pos() {
-                                    arrow.end = mouse_position;
-                                    arrow.draw(ui.painter());
+                                    arrow.end = self.viewport.to_world(mouse_position);
+                                    arrow.draw(&painter, transform);
                                 }
-
                                 if ui.input(|i| i.pointer.primary_clicked()) {
                                     if let Some(mut arrow) = self.dragging_arrow.take() {
                                         for maybe_node in &self.nodes {
@@ -992,7 +1813,8 @@ impl eframe::App for NodeEditor {
                                                     arrow.id_to_node = Some(node.id);
                                                     if !is_present(&self.arrows, &arrow) {
                                                         arrow.end = node.get_input_edge();
-                                                        self.insert_new_arrow(arrow);
+                                                        let id = self.insert_new_arrow(arrow);
+                                                        self.history.record(Command::AddArrow(self.arrows[id].clone().unwrap()));
                                                         break;  
                                                     }
                                                 }
@@ -1006,9 +1828,35 @@ impl eframe::App for NodeEditor {
                                     let (from, to) = (arrow.id_from_node, arrow.id_to_node.unwrap());
                                     arrow.start = self.nodes[from].as_ref().unwrap().get_output_edge();
                                     arrow.end = self.nodes[to].as_ref().unwrap().get_input_edge();
-                                    arrow.draw(ui.painter());
+                                    arrow.draw(&painter, transform);
+                                    if let Some((q, read, t)) = &self.fired
+                                        && (*q, *t) == (from, to)
+                                        && let Some(index) = arrow.labels.iter().position(|l| {
+                                            label::split(l, self.n_tapes as usize).is_ok_and(|(reads, _)| label::join(&reads) == *read)
+                                        })
+                                    {
+                                        let fade = ((ui.input(|i| i.time) - self.fired_at) / FLASH_SECONDS).min(1.0) as f32;
+                                        arrow.highlight_label(&painter, transform, index, Color32::WHITE.lerp_to_gamma(CURRENT_STATE, fade));
+                                    }
                                 }
                             }
+                            if let Some(start) = self.band_start
+                                && let Some(pointer) = ui.ctx().pointer_latest_pos()
+                            {
+                                let band = Rect::from_two_pos(start, self.viewport.to_world(pointer));
+                                if response.drag_stopped() {
+                                    let mut nodes = if shift { self.selected_nodes.clone() } else { BTreeSet::new() };
+                                    nodes.extend(self.nodes.iter().flatten().filter(|n| band.intersects(n.rect())).map(|n| n.id));
+                                    self.select_nodes(nodes);
+                                } else {
+                                    painter.rect(transform * band, 0.0, SELECTION.gamma_multiply(0.15), egui::Stroke::new(1.0, SELECTION), egui::StrokeKind::Inside);
+                                }
+                            }
+                            if response.drag_stopped() { self.band_start = None; }
+                            if self.nodes.iter().any(|n| n.is_some()) {
+                                minimap::show(ui, group_rect, &self.nodes, &self.arrows, &mut self.viewport, self.current_state);
+                            }
+                            self.viewport.pan(pan);
 
                         });
                     });
@@ -1074,13 +1922,17 @@ impl eframe::App for NodeEditor {
                                                             .font(egui::FontId::monospace(20.0)
                                                         ).background_color(BG[i & 0b1])
                                                     );
-                                                    ui.add(
+                                                    let old = label.clone();
+                                                    let response = ui.add(
                                                         egui::TextEdit::singleline(label)
                                                             .font(egui::FontId::monospace(20.0))
                                                             .desired_width(100.0)
                                                             .background_color(Color32::TRANSPARENT)
                                                     );
-                                                    if label.len() > 2 * self.n_tapes as usize + 1 { label.truncate(2 * self.n_tapes as usize + 1) }
+                                                    if response.changed() {
+                                                        self.history.record(Command::EditLabel { arrow: i, index: j, old, new: label.clone() });
+                                                    }
+                                                    if response.lost_focus() { self.history.seal(); }
                                                     if ui.button("X").clicked() {
                                                         self.to_remove_next_frame = Some((i, j));
                                                     }
@@ -1103,6 +1955,30 @@ impl eframe::App for NodeEditor {
                                         .desired_width(size.x * 0.9)
                                         .background_color(Color32::TRANSPARENT)
                                 );
+                                ui.horizontal(|ui| {
+                                    if ui.button(egui::RichText::new("Save input").font(egui::FontId::monospace(15.0))).clicked()
+                                        && !self.saved_inputs.contains(&self.input)
+                                    {
+                                        self.saved_inputs.push(self.input.clone());
+                                    }
+                                    ui.menu_button(egui::RichText::new("Saved inputs").font(egui::FontId::monospace(15.0)), |ui| {
+                                        if self.saved_inputs.is_empty() {
+                                            ui.label(egui::RichText::new("No saved inputs").font(egui::FontId::monospace(15.0)));
+                                        }
+                                        let mut to_remove = None;
+                                        for (i, saved) in self.saved_inputs.iter().enumerate() {
+                                            ui.horizontal(|ui| {
+                                                let text = if saved.is_empty() { "(empty)" } else { saved.as_str() };
+                                                if ui.button(egui::RichText::new(text).font(egui::FontId::monospace(15.0))).clicked() {
+                                                    self.input = saved.clone();
+                                                    ui.close();
+                                                }
+                                                if ui.button("X").clicked() { to_remove = Some(i); }
+                                            });
+                                        }
+                                        if let Some(i) = to_remove { self.saved_inputs.remove(i); }
+                                    });
+                                });
                             });
                         });
                     });
@@ -1113,6 +1989,9 @@ impl eframe::App for NodeEditor {
 }
 
 const STARTING_POSITION: Pos2 = Pos2::new(50.0, 150.0);
+const NODE_SIZE: egui::Vec2 = egui::vec2(100.0, 100.0);
+/// Screen pixels from an arrow that still select it
+const ARROW_HIT_DISTANCE: f32 = 6.0;
 fn make_node(id: usize, header: bool) -> Node {
     Node::new(
         id,
@@ -1132,6 +2011,10 @@ fn make_arrow(id: usize, start: Pos2, end: Pos2, from: usize) -> Arrow {
     )  
 }
 
+fn clear_slot<T>(slots: &mut [Option<T>], id: usize) {
+    if let Some(slot) = slots.get_mut(id) { *slot = None; }
+}
+
 fn is_present(arrows: &Vec<Option<Arrow>>, arrow: &Arrow) -> bool {
     assert!(arrow.id_to_node.is_some());
     let (from, to) = (arrow.id_from_node, arrow.id_to_node.unwrap());
@@ -1143,3 +2026,124 @@ fn is_present(arrows: &Vec<Option<Arrow>>, arrow: &Arrow) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor_with_nodes(count: usize) -> NodeEditor {
+        let mut editor = NodeEditor::new();
+        for id in 0..count { editor.insert_new_node(make_node(id, false)); }
+        editor
+    }
+
+    fn color(editor: &NodeEditor, id: usize) -> Color32 {
+        editor.nodes[id].as_ref().unwrap().foreground_color
+    }
+
+    #[test]
+    fn undoing_final_keeps_the_custom_color() {
+        let mut editor = editor_with_nodes(2);
+        editor.recolor(&[0], Color32::RED);
+        editor.toggle_final(&[0, 1]);
+        assert_eq!((color(&editor, 0), color(&editor, 1)), (FG_1, FG_1));
+
+        editor.undo();
+        assert_eq!((color(&editor, 0), color(&editor, 1)), (Color32::RED, FG));
+        assert!(!editor.nodes[0].as_ref().unwrap().is_final);
+        editor.redo();
+        assert_eq!(color(&editor, 0), FG_1);
+        assert!(editor.nodes[0].as_ref().unwrap().is_final);
+    }
+
+    fn label(editor: &NodeEditor, id: usize) -> &str {
+        &editor.nodes[id].as_ref().unwrap().label
+    }
+
+    fn rename(editor: &mut NodeEditor, id: usize, new: &str) {
+        let node = editor.nodes[id].as_mut().unwrap();
+        let old = std::mem::replace(&mut node.label, new.to_owned());
+        editor.history.record(Command::SetNodeLabel { id, old, new: new.to_owned() });
+    }
+
+    #[test]
+    fn renaming_after_another_selection_is_a_new_step() {
+        let mut editor = editor_with_nodes(2);
+        editor.select_nodes(BTreeSet::from([0]));
+        rename(&mut editor, 0, "q");
+        rename(&mut editor, 0, "q0");
+        editor.select_nodes(BTreeSet::from([1]));
+        editor.select_nodes(BTreeSet::from([0]));
+        rename(&mut editor, 0, "q01");
+
+        editor.undo();
+        assert_eq!(label(&editor, 0), "q0");
+        editor.undo();
+        assert_eq!(label(&editor, 0), "");
+    }
+
+    #[test]
+    fn undoing_a_delete_brings_the_arrows_back() {
+        let mut editor = editor_with_nodes(3);
+        for (from, to) in [(0, 1), (1, 2), (2, 0)] {
+            editor.insert_new_arrow(Arrow::new(0, Pos2::ZERO, Pos2::ZERO, from, Some(to)));
+        }
+        editor.delete_node(1);
+        assert!(editor.nodes[1].is_none());
+        assert_eq!(editor.arrows.iter().flatten().count(), 1);
+
+        editor.undo();
+        assert!(editor.nodes[1].is_some());
+        let arrows: Vec<_> = editor.arrows.iter().flatten().map(|a| (a.id, a.id_from_node, a.id_to_node)).collect();
+        assert_eq!(arrows, [(0, 0, Some(1)), (1, 1, Some(2)), (2, 2, Some(0))]);
+        editor.redo();
+        assert_eq!(editor.arrows.iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn a_group_is_undone_backwards() {
+        let mut editor = editor_with_nodes(1);
+        let (a, b, c) = (STARTING_POSITION, Pos2::new(200.0, 0.0), Pos2::new(400.0, 0.0));
+        let group = Command::Group(vec![
+            Command::MoveNode { id: 0, from: a, to: b },
+            Command::MoveNode { id: 0, from: b, to: c },
+        ]);
+        editor.apply(&group, true);
+        assert_eq!(editor.nodes[0].as_ref().unwrap().top_left, c);
+        editor.apply(&group, false);
+        assert_eq!(editor.nodes[0].as_ref().unwrap().top_left, a);
+    }
+
+    #[test]
+    fn replace_round_trip() {
+        let mut editor = editor_with_nodes(2);
+        rename(&mut editor, 0, "q0");
+        editor.insert_new_arrow(Arrow::new(0, Pos2::ZERO, Pos2::ZERO, 0, Some(1)));
+        editor.input = String::from("ab");
+        let before = formats::text::print(&editor.describe());
+
+        editor.replace_with(|editor| {
+            editor.remove_node(1);
+            editor.input = String::from("ba");
+        }, false);
+        let after = formats::text::print(&editor.describe());
+        assert_ne!(before, after);
+
+        editor.undo();
+        assert_eq!(formats::text::print(&editor.describe()), before);
+        assert_eq!(editor.input, "ab");
+        editor.redo();
+        assert_eq!(formats::text::print(&editor.describe()), after);
+        assert_eq!(editor.input, "ba");
+    }
+
+    #[test]
+    fn a_long_blank_is_refused() {
+        let mut editor = editor_with_nodes(1);
+        editor.def_blank = String::from("x0");
+        assert!(editor.build_machine::<1>().err().is_some_and(|e| e.contains("'x0'")));
+        assert_eq!(editor.def_blank, "x0");
+        editor.def_blank = String::from("x");
+        assert!(editor.build_machine::<1>().is_ok());
+    }
+}
@@ -0,0 +1,57 @@
+//! Part of the workspace shown on the canvas. Nodes and arrows keep world coordinates,
+//! the transform takes them to the screen, so the workspace has no bounds.
+
+use egui::emath::TSTransform;
+use egui::{Pos2, Rect, Vec2};
+
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 4.0;
+/// Space left around the diagram by `fit`
+const FIT_MARGIN: f32 = 30.0;
+
+#[derive(Default)]
+pub struct Viewport {
+    pub transform: TSTransform,
+}
+
+impl Viewport {
+    pub fn zoom(&self) -> f32 {
+        self.transform.scaling
+    }
+
+    pub fn to_world(&self, screen: Pos2) -> Pos2 {
+        self.transform.inverse() * screen
+    }
+
+    pub fn to_world_rect(&self, screen: Rect) -> Rect {
+        self.transform.inverse() * screen
+    }
+
+    /// Moves the view by a distance in screen pixels
+    pub fn pan(&mut self, delta: Vec2) {
+        self.transform.translation += delta;
+    }
+
+    /// Zooms keeping `center`, a screen position, where it is
+    pub fn zoom_at(&mut self, center: Pos2, factor: f32) {
+        let factor = (self.zoom() * factor).clamp(MIN_ZOOM, MAX_ZOOM) / self.zoom();
+        self.transform = TSTransform::from_translation(center.to_vec2())
+            * TSTransform::from_scaling(factor)
+            * TSTransform::from_translation(-center.to_vec2())
+            * self.transform;
+    }
+
+    /// Puts the `world` position at the center of `screen`
+    pub fn center_on(&mut self, world: Pos2, screen: Rect) {
+        self.transform.translation = screen.center().to_vec2() - world.to_vec2() * self.zoom();
+    }
+
+    /// Shows all of `world` inside `screen`
+    pub fn fit(&mut self, world: Rect, screen: Rect) {
+        if !world.is_finite() || !screen.is_finite() { return }
+        let available = (screen.size() - Vec2::splat(2.0 * FIT_MARGIN)).max(Vec2::splat(1.0));
+        let size = world.size().max(Vec2::splat(1.0));
+        let zoom = (available.x / size.x).min(available.y / size.y).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.transform = TSTransform::new(screen.center().to_vec2() - world.center().to_vec2() * zoom, zoom);
+    }
+}
@@ -1,7 +1,8 @@
 use super::definitions::{RSymbol, LSymbol};
 use std::collections::HashMap;
-pub const DEFAULT_BLANK: RSymbol = '*';
+pub const DEFAULT_BLANK: char = '*';
 
+/// Symbols are strings, a character or a longer name like `x1`
 pub struct Alphabet {
     blank_r_symbol: RSymbol,
     l_to_r: HashMap<LSymbol, RSymbol>,
@@ -12,12 +13,12 @@ impl Alphabet {
 
     pub fn new(blank_r_symbol: RSymbol) -> Self {
         let mut alpha = Alphabet {
-            blank_r_symbol: blank_r_symbol,
+            blank_r_symbol: blank_r_symbol.clone(),
             l_to_r: HashMap::new(),
             r_to_l: HashMap::new()
         };
 
-        alpha.add_symbol(alpha.blank_r_symbol).ok();
+        alpha.add_symbol(blank_r_symbol).ok();
         
         alpha
     }
@@ -26,7 +27,7 @@ impl Alphabet {
         if self.r_to_l.contains_key(&symbol) { return Ok(()) }
         let current_count /* also new index */ = self.l_to_r.len() as u8;
 
-        self.l_to_r.insert(current_count, symbol);
+        self.l_to_r.insert(current_count, symbol.clone());
         self.r_to_l.insert(symbol, current_count);
 
         Ok(())
@@ -35,7 +36,7 @@ impl Alphabet {
     pub fn add_symbols(&mut self, symbols: &[RSymbol]) -> (u8, u8) {
         let (mut inserted, mut rejected) = (0u8, 0u8);
         for r_symbol in symbols {
-            match self.add_symbol(*r_symbol) {
+            match self.add_symbol(r_symbol.clone()) {
                 Ok(())  => { inserted += 1 }
                 Err(()) => { rejected += 1 }
             }
@@ -43,21 +44,26 @@ impl Alphabet {
         (inserted, rejected)
     }
 
-    pub fn get_l_symbol(&self, r_key: &RSymbol) -> Option<LSymbol> {
-        self.r_to_l.get(r_key).map(|l| *l)
+    pub fn get_l_symbol(&self, r_key: &str) -> Option<LSymbol> {
+        self.r_to_l.get(r_key).copied()
     }
-    pub fn get_r_symbol(&self, l_key: &LSymbol) -> Option<RSymbol> {
-        self.l_to_r.get(l_key).map(|r| *r)
+    pub fn get_r_symbol(&self, l_key: &LSymbol) -> Option<&str> {
+        self.l_to_r.get(l_key).map(|r| r.as_str())
     }
 
     pub fn get_l_symbols(&self, r_keys: &[RSymbol]) -> Vec<Option<LSymbol>> {
         r_keys.iter().map(|r| self.get_l_symbol(r)).collect()
     }
-    pub fn get_r_symbols(&self, l_keys: &[LSymbol]) -> Vec<Option<RSymbol>> {
+    pub fn get_r_symbols(&self, l_keys: &[LSymbol]) -> Vec<Option<&str>> {
         l_keys.iter().map(|l| self.get_r_symbol(l)).collect()
     }
 
-    pub fn default_blank(&self) -> RSymbol { self.blank_r_symbol }
+    pub fn default_blank(&self) -> &str { &self.blank_r_symbol }
+
+    /// Every symbol in the order they were added, the blank first
+    pub fn symbols(&self) -> Vec<&str> {
+        (0..self.l_to_r.len() as LSymbol).filter_map(|l| self.get_r_symbol(&l)).collect()
+    }
 
     pub fn len(&self) -> usize { self.l_to_r.len() }
-}
\ No newline at end of file
+}
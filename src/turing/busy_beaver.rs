@@ -0,0 +1,182 @@
+//! Standard text notation for busy beaver machines, e.g. `1RB1LB_1LA1RZ`.
+//!
+//! States are separated by `_`, each one lists what happens on symbol 0, 1, ... as
+//! write, move, next state. `---` is an undefined transition and `Z` halts.
+//! Symbol 0 is the blank.
+
+use super::definitions::{State, LSymbol};
+use super::TuringMachine;
+use std::collections::HashMap;
+
+pub const HALT: char = 'Z';
+pub const UNDEFINED: &str = "---";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub write: LSymbol,
+    pub right: bool,
+    /// `None` halts
+    pub next: Option<State>,
+}
+
+/// One row of entries per state, one entry per symbol
+pub struct Table {
+    pub states: Vec<Vec<Option<Entry>>>,
+}
+
+pub fn state_letter(q: usize) -> char {
+    (b'A' + q as u8) as char
+}
+
+impl Table {
+
+    pub fn parse(notation: &str) -> Result<Self, String> {
+        let rows: Vec<&str> = notation.trim().split('_').collect();
+        if rows.len() >= 26 { return Err(format!("{} states, at most 25 fit in the notation", rows.len())) }
+        let symbol_count = rows[0].len() / 3;
+        if symbol_count == 0 || symbol_count > 10 { return Err("Every state needs between 1 and 10 entries of 3 characters".to_owned()) }
+
+        let mut states = vec![];
+        for (q, row) in rows.iter().enumerate() {
+            let chars: Vec<char> = row.chars().collect();
+            if chars.len() != 3 * symbol_count {
+                return Err(format!("State {} has {} characters, expected {}", state_letter(q), chars.len(), 3 * symbol_count))
+            }
+            let mut entries = vec![];
+            for (x, entry) in chars.chunks(3).enumerate() {
+                let at = |e: &str| format!("State {} on {}: {}", state_letter(q), x, e);
+                if entry.iter().collect::<String>() == UNDEFINED { entries.push(None); continue }
+                let write = entry[0].to_digit(10).filter(|w| (*w as usize) < symbol_count)
+                    .ok_or(at(&format!("'{}' is not one of the {} symbols", entry[0], symbol_count)))?;
+                let right = match entry[1] {
+                    'R' => true,
+                    'L' => false,
+                    other => return Err(at(&format!("'{}' is not a direction, expected L or R", other))),
+                };
+                let next = match entry[2] {
+                    HALT => None,
+                    c @ 'A'..='Y' => match c as u8 - b'A' {
+                        t if (t as usize) < rows.len() => Some(t),
+                        _ => return Err(at(&format!("state {} is not in the table", c))),
+                    },
+                    other => return Err(at(&format!("'{}' is not a state letter", other))),
+                };
+                entries.push(Some(Entry { write: write as LSymbol, right, next }));
+            }
+            states.push(entries);
+        }
+        Ok(Table { states })
+    }
+
+    pub fn symbol_count(&self) -> usize {
+        self.states.first().map(|row| row.len()).unwrap_or(0)
+    }
+
+    /// The machine moves and writes in different steps, so states `0..n` are the ones in
+    /// the table, then comes the final halting state if some entry uses it and then the
+    /// states that move after a write, one per direction and target. Halting entries move
+    /// too before reaching the final state, so their direction is kept.
+    pub fn to_machine(&self) -> Result<TuringMachine<1>, String> {
+        let n = self.states.len();
+        let halts = self.states.iter().flatten().flatten().any(|e| e.next.is_none());
+        let first_step = n + halts as usize;
+        let target = |entry: &Entry| entry.next.unwrap_or(n as State);
+
+        let mut steps = HashMap::<(bool, State), usize>::new();
+        for row in &self.states {
+            for (x, entry) in row.iter().enumerate() {
+                let Some(entry) = entry else { continue };
+                if entry.write as usize != x {
+                    let count = steps.len();
+                    steps.entry((entry.right, target(entry))).or_insert(first_step + count);
+                }
+            }
+        }
+
+        let symbol_count = self.symbol_count();
+        let mut m = TuringMachine::<1>::new(first_step + steps.len(), symbol_count).map_err(|e| format!("{:?}", e))?;
+        let direction = |right: bool| if right { m.dx() } else { m.sx() };
+        let mut transitions = vec![];
+        for (q, row) in self.states.iter().enumerate() {
+            for (x, entry) in row.iter().enumerate() {
+                let Some(entry) = entry else { continue };
+                let (q, x) = (q as State, x as LSymbol);
+                transitions.push(if entry.write == x {
+                    (q, x, direction(entry.right), target(entry))
+                } else {
+                    (q, x, entry.write, steps[&(entry.right, target(entry))] as State)
+                });
+            }
+        }
+        for ((right, next), step) in &steps {
+            for x in 0..symbol_count {
+                transitions.push((*step as State, x as LSymbol, direction(*right), *next));
+            }
+        }
+
+        for (q, x, a, t) in transitions {
+            m.add_transition(q, [x], [a], t).map_err(|e| format!("{:?}", e))?;
+        }
+        if halts { m.add_final_state(n as State).map_err(|e| format!("{:?}", e))?; }
+        Ok(m)
+    }
+
+    /// Reads back a machine where each write is followed by a move. Final states halt,
+    /// the others reachable from state 0 become the rows of the table. A write straight
+    /// into a final state has no direction, it is written as a move right.
+    pub fn from_machine(m: &TuringMachine<1>) -> Result<Self, String> {
+        let symbol_count = m.dx() as usize;
+        if symbol_count > 10 { return Err(format!("{} symbols, at most 10 fit in the notation", symbol_count)) }
+        let is_final = |q: State| m.is_final_state(q).unwrap_or(false);
+        if is_final(0) { return Err("The start state is final, the machine would never run".to_owned()) }
+
+        let mut order: Vec<State> = vec![0];
+        let mut states = vec![];
+        let mut i = 0;
+        while i < order.len() {
+            let q = order[i];
+            i += 1;
+            let mut row = vec![];
+            for x in 0..symbol_count as LSymbol {
+                let Ok((t, [a])) = m.get_transition(q, &[x]) else { row.push(None); continue };
+                let (write, right, next) = if a == m.dx() || a == m.sx() {
+                    (x, a == m.dx(), t)
+                } else if is_final(t) {
+                    (a, true, t)
+                } else {
+                    match m.get_transition(t, &[a]) {
+                        Ok((next, [d])) if d == m.dx() || d == m.sx() => (a, d == m.dx(), next),
+                        _ => return Err(format!("q{} writes on {} but q{} does not move after", q, x, t)),
+                    }
+                };
+                let next = if is_final(next) {
+                    None
+                } else {
+                    let index = order.iter().position(|s| *s == next).unwrap_or_else(|| { order.push(next); order.len() - 1 });
+                    Some(index as State)
+                };
+                row.push(Some(Entry { write, right, next }));
+            }
+            states.push(row);
+        }
+        if states.len() >= 26 { return Err(format!("{} states, at most 25 fit in the notation", states.len())) }
+        Ok(Table { states })
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows: Vec<String> = self.states.iter().map(|row| {
+            row.iter().map(|entry| match entry {
+                None => UNDEFINED.to_owned(),
+                Some(e) => format!(
+                    "{}{}{}",
+                    e.write,
+                    if e.right { 'R' } else { 'L' },
+                    e.next.map(|t| state_letter(t as usize)).unwrap_or(HALT)
+                ),
+            }).collect()
+        }).collect();
+        write!(f, "{}", rows.join("_"))
+    }
+}
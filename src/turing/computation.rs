@@ -1,4 +1,5 @@
-use super::{Alphabet, Tape, TuringMachine, LSymbol};
+use super::{Alphabet, Tape, TuringMachine, LSymbol, RSymbol, State};
+use std::collections::{HashSet, VecDeque};
 use std::sync::{
     Arc,
     Condvar,
@@ -24,6 +25,14 @@ pub struct Computation<const K: usize> {
     tapes: Option<[Tape; K]>,
     m: Option<TuringMachine<K>>,
 
+    /// Transitions fired since the machine was set, as state, symbols read and next state
+    visited: HashSet<(State, [LSymbol; K], State)>,
+    /// States left by the latest transitions, the last one is the most recent
+    recent: VecDeque<State>,
+    last: Option<(State, [LSymbol; K], State)>,
+    /// Tapes changed by hand since the machine was set
+    edits: Vec<ManualEdit>,
+
     current: Arc<AtomicU8>,
     transition_count: Arc<AtomicUsize>,
 
@@ -39,6 +48,58 @@ pub struct Computation<const K: usize> {
     done_rx:    Option<Receiver<()>>
 }
 
+/// States kept by `recent`
+pub const RECENT_LIMIT: usize = 32;
+
+/// Where a computation is, read at once so that a running one is seen in a single state
+pub struct Progress {
+    pub current: State,
+    /// The transition that led to `current`, with the symbols read written like in labels
+    pub last: Option<(State, String, State)>,
+    /// States visited before `current`, the most recent last
+    pub recent: Vec<State>,
+    pub transition_count: usize,
+}
+
+/// Symbols of a tape as they are written in labels
+pub struct TapeContent {
+    pub cells: Vec<String>,
+    pub head: usize,
+}
+
+#[derive(Clone)]
+pub enum TapeEdit {
+    Write { tape: usize, cell: usize, symbol: RSymbol },
+    MoveHead { tape: usize, cell: usize },
+}
+
+impl std::fmt::Display for TapeEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TapeEdit::Write { tape, cell, symbol } => write!(f, "tape {}, cell {} set to {}", tape + 1, cell, symbol),
+            TapeEdit::MoveHead { tape, cell } => write!(f, "tape {}, head moved to cell {}", tape + 1, cell),
+        }
+    }
+}
+
+/// A change made by hand, `before` is the edit that would undo it
+#[derive(Clone)]
+pub struct ManualEdit {
+    /// Transitions fired before the change
+    pub step: usize,
+    pub edit: TapeEdit,
+    pub before: TapeEdit,
+}
+
+impl std::fmt::Display for ManualEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.before {
+            TapeEdit::Write { symbol, .. } => write!(f, "after {} steps: {}, was {}", self.step, self.edit, symbol),
+            TapeEdit::MoveHead { cell, .. } => write!(f, "after {} steps: {}, was on cell {}", self.step, self.edit, cell),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum StepFeedback {
     CanContinue, NeedToStop
@@ -52,6 +113,11 @@ impl<const K: usize> Computation<K> {
             tapes: None,
             m: None,
 
+            visited: HashSet::new(),
+            recent: VecDeque::new(),
+            last: None,
+            edits: vec![],
+
             current: Arc::new(AtomicU8::new(0)),
             transition_count: Arc::new(AtomicUsize::new(0)),
 
@@ -79,6 +145,10 @@ impl<const K: usize> Computation<K> {
     }
     pub fn use_machine(&mut self, m: TuringMachine<K>) {
         self.m = Some(m);
+        self.visited.clear();
+        self.recent.clear();
+        self.last = None;
+        self.edits.clear();
     }
     pub fn use_tape(&mut self, t: Tape, idx: usize) {
         assert!(idx < K);
@@ -111,7 +181,7 @@ impl<const K: usize> Computation<K> {
         for i in 0..K { self.shift_head(0, i); }
         let alpha = self.alphabet.as_ref().ok_or("An alphabet is needed!")?;
         let tapes = self.tapes.as_mut().ok_or("Tapes are needed")?;
-        for r_symbol in self.w.chars() {
+        for r_symbol in super::label::split_input(&self.w)? {
             let l_symbol = alpha.get_l_symbol(&r_symbol).ok_or(format!("An error occurred between alphabet and input string, {} is not in the alphabet", r_symbol))?;
             tapes[0].write(l_symbol);
             for i in 1..K { tapes[i].write(super::BLANK); }
@@ -120,24 +190,74 @@ impl<const K: usize> Computation<K> {
         Ok(())
     }
 
+    /// The tape followed by a line with `^` under the head. Symbols longer than a
+    /// character get cells of the same width separated by spaces.
     pub fn output(&mut self, idx: usize) -> Result<String, String> {
         if idx >= K { return Err("Index out of bounds".to_owned()) }
-        let default = self.alphabet.as_ref().ok_or("No alphabet!").unwrap().default_blank();
-        let tape = self.tapes.as_mut().ok_or("No tape")?.get(idx).ok_or("No tape")?;
-        let r_symbols = self.alphabet.as_ref().unwrap().get_r_symbols(tape.content());
-        let mut r_tape = String::with_capacity(tape.size() + 4 + tape.head_position());
-        
-        for r_symbol in r_symbols {
-            r_tape.push(r_symbol.unwrap_or(default));
+        let alpha = self.alphabet.as_ref().ok_or("No alphabet!")?;
+        let tape = self.tapes.as_ref().ok_or("No tape")?.get(idx).ok_or("No tape")?;
+        let cells: Vec<&str> = alpha.get_r_symbols(tape.content()).into_iter()
+            .map(|r_symbol| r_symbol.unwrap_or(alpha.default_blank()))
+            .collect();
+        let width = cells.iter().map(|c| c.chars().count()).max().unwrap_or(1);
+        let gap = if width > 1 { " " } else { "" };
+        let mut r_tape = String::with_capacity((width + gap.len()) * (tape.size() + tape.head_position()) + 4);
+
+        for cell in cells {
+            r_tape.push_str(&format!("{:<width$}{}", cell, gap, width = width));
         }
         r_tape.push_str("...\n");
-        for _ in 0..tape.head_position() {
+        for _ in 0..tape.head_position() * (width + gap.len()) {
             r_tape.push(' ');
         }
         r_tape.push('^');
 
         Ok(r_tape)
     }
+    /// Every tape, the first one first
+    pub fn tapes_content(&self) -> Result<Vec<TapeContent>, String> {
+        let alpha = self.alphabet.as_ref().ok_or("No alphabet!")?;
+        let tapes = self.tapes.as_ref().ok_or("No tapes")?;
+        Ok(tapes.iter().map(|tape| TapeContent {
+            cells: alpha.get_r_symbols(tape.content()).into_iter()
+                .map(|r_symbol| r_symbol.unwrap_or(alpha.default_blank()).to_owned())
+                .collect(),
+            head: tape.head_position(),
+        }).collect())
+    }
+    pub fn symbols(&self) -> Vec<RSymbol> {
+        self.alphabet.as_ref().map(|alpha| alpha.symbols().into_iter().map(str::to_owned).collect()).unwrap_or_default()
+    }
+
+    /// Writes a symbol or moves a head by hand, the change is kept in `manual_edits`.
+    /// Tapes that don't grow can only be edited up to their end.
+    pub fn edit_tape(&mut self, edit: TapeEdit) -> Result<(), String> {
+        let alpha = self.alphabet.as_ref().ok_or("No alphabet!")?;
+        let tapes = self.tapes.as_mut().ok_or("No tapes")?;
+        let (TapeEdit::Write { tape, cell, .. } | TapeEdit::MoveHead { tape, cell }) = edit;
+        let t = tapes.get_mut(tape).ok_or("No tape")?;
+        let past_end = || format!("Tape {} does not grow up to cell {}", tape + 1, cell);
+        let before = match &edit {
+            TapeEdit::Write { symbol, .. } => {
+                let x = alpha.get_l_symbol(symbol).ok_or(format!("{} is not in the alphabet", symbol))?;
+                let old = t.content().get(cell).and_then(|l| alpha.get_r_symbol(l)).unwrap_or(alpha.default_blank()).to_owned();
+                t.write_at(cell, x).map_err(|_| past_end())?;
+                TapeEdit::Write { tape, cell, symbol: old }
+            }
+            TapeEdit::MoveHead { .. } => {
+                let old = t.head_position();
+                t.set_head(cell).map_err(|_| past_end())?;
+                TapeEdit::MoveHead { tape, cell: old }
+            }
+        };
+        self.edits.push(ManualEdit { step: self.transition_count.load(Ordering::SeqCst), edit, before });
+        Ok(())
+    }
+
+    pub fn manual_edits(&self) -> &[ManualEdit] {
+        &self.edits
+    }
+
     pub fn output_all(&mut self) -> Result<String, String> {
         let tapes = self.tapes.as_mut().ok_or("No tapes")?;
         let mut string_size = 0;
@@ -177,6 +297,8 @@ impl<const K: usize> Computation<K> {
 
         let (paused, stopped, terminated, mtx, cv, tx, rx) = {
             let mut c = me.lock().unwrap();
+            c.recent.clear();
+            c.last = None;
 
             let paused: Arc<AtomicBool> = Arc::clone(&c.paused);
             let stopped = Arc::clone(&c.stopped);
@@ -214,10 +336,7 @@ impl<const K: usize> Computation<K> {
                     match c.step() {
                         Ok(how) => {
                             match how {
-                                StepFeedback::CanContinue => { 
-                                    let x = c.transition_count.load(Ordering::SeqCst);
-                                    c.transition_count.store(x + 1, Ordering::SeqCst);
-                                }
+                                StepFeedback::CanContinue => {}
                                 StepFeedback::NeedToStop => {
                                     c.terminated.store(true, Ordering::SeqCst);
                                     break 'main;
@@ -279,6 +398,31 @@ impl<const K: usize> Computation<K> {
         }
     }
 
+    pub fn current_state(&self) -> State {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    fn read_symbols(&self, x: &[LSymbol; K]) -> String {
+        let Some(alpha) = &self.alphabet else { return String::new() };
+        let read: Vec<String> = x.iter().map(|l| alpha.get_r_symbol(l).unwrap_or(alpha.default_blank()).to_owned()).collect();
+        super::label::join(&read)
+    }
+
+    /// Fired transitions with the symbols read written back like in labels
+    pub fn visited_transitions(&self) -> Vec<(State, String, State)> {
+        if self.alphabet.is_none() { return vec![] }
+        self.visited.iter().map(|(q, x, t)| (*q, self.read_symbols(x), *t)).collect()
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            current: self.current_state(),
+            last: self.last.as_ref().map(|(q, x, t)| (*q, self.read_symbols(x), *t)),
+            recent: self.recent.iter().copied().collect(),
+            transition_count: self.transition_count.load(Ordering::SeqCst),
+        }
+    }
+
     pub fn step(&mut self) -> Result<StepFeedback, String> {
 
         let m = self.m.as_mut().ok_or("No machine")?;
@@ -288,6 +432,7 @@ impl<const K: usize> Computation<K> {
         for i in 0..K {
             x[i] = tapes[i].read();
         }
+        let x_read = x;
         let maybe_out = m.get_transition(self.current.load(Ordering::SeqCst), &x);
         if let Ok(out) = maybe_out {
             x = out.1;
@@ -302,11 +447,73 @@ impl<const K: usize> Computation<K> {
                     tapes[i].write(x[i]);
                 }
             }
+            let from = self.current.load(Ordering::SeqCst);
+            self.visited.insert((from, x_read, out.0));
+            self.last = Some((from, x_read, out.0));
+            self.recent.push_back(from);
+            if self.recent.len() > RECENT_LIMIT { self.recent.pop_front(); }
             self.current.store(out.0, Ordering::SeqCst);
+            self.transition_count.fetch_add(1, Ordering::SeqCst);
 
             Ok(StepFeedback::CanContinue)
         } else {
             Ok(StepFeedback::NeedToStop)
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::BLANK;
+
+    /// One tape holding `a a *`, the machine moves right over `a`
+    fn computation(extend_on_end: bool) -> Computation<1> {
+        let mut alphabet = Alphabet::new("*".to_owned());
+        alphabet.add_symbol("a".to_owned()).ok();
+        alphabet.add_symbol("x1".to_owned()).ok();
+        let a = alphabet.get_l_symbol("a").unwrap();
+        let mut m = TuringMachine::<1>::new(1, alphabet.len()).unwrap();
+        m.add_transition(0, [a], [m.dx()], 0).unwrap();
+
+        let mut c = Computation::<1>::new();
+        c.use_alphabet(alphabet);
+        c.use_machine(m);
+        c.use_tapes([Tape::with_content(vec![a, a, BLANK], extend_on_end)]);
+        c
+    }
+
+    #[test]
+    fn edits_are_recorded_with_what_they_replaced() {
+        let mut c = computation(false);
+        c.step().unwrap();
+        c.edit_tape(TapeEdit::Write { tape: 0, cell: 1, symbol: "x1".to_owned() }).unwrap();
+        c.edit_tape(TapeEdit::MoveHead { tape: 0, cell: 2 }).unwrap();
+
+        let tape = &c.tapes_content().unwrap()[0];
+        assert_eq!(tape.cells, ["a", "x1", "*"]);
+        assert_eq!(tape.head, 2);
+
+        let edits = c.manual_edits();
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].step, 1);
+        assert!(matches!(&edits[0].before, TapeEdit::Write { tape: 0, cell: 1, symbol } if symbol == "a"));
+        assert!(matches!(edits[1].before, TapeEdit::MoveHead { tape: 0, cell: 1 }));
+        assert_eq!(edits[0].to_string(), "after 1 steps: tape 1, cell 1 set to x1, was a");
+    }
+
+    #[test]
+    fn edits_past_the_end_of_a_tape_that_does_not_grow_are_refused() {
+        let mut c = computation(false);
+        assert!(c.edit_tape(TapeEdit::Write { tape: 0, cell: 3, symbol: "a".to_owned() }).is_err());
+        assert!(c.edit_tape(TapeEdit::MoveHead { tape: 0, cell: 3 }).is_err());
+        assert!(c.edit_tape(TapeEdit::Write { tape: 0, cell: 0, symbol: "b".to_owned() }).is_err());
+        assert!(c.edit_tape(TapeEdit::MoveHead { tape: 1, cell: 0 }).is_err());
+        assert!(c.manual_edits().is_empty());
+        assert_eq!(c.tapes_content().unwrap()[0].cells, ["a", "a", "*"]);
+
+        let mut c = computation(true);
+        c.edit_tape(TapeEdit::Write { tape: 0, cell: 4, symbol: "a".to_owned() }).unwrap();
+        assert_eq!(c.tapes_content().unwrap()[0].cells, ["a", "a", "*", "*", "a"]);
+        assert!(matches!(&c.manual_edits()[0].before, TapeEdit::Write { symbol, .. } if symbol == "*"));
+    }
+}
@@ -1,4 +1,4 @@
 pub type State = u8;
 pub type LSymbol = u8;
-pub type RSymbol = char;
+pub type RSymbol = String;
 pub const BLANK: LSymbol = 0;
@@ -0,0 +1,123 @@
+//! How symbols are written in labels and inputs.
+//!
+//! A label is `reads/actions` with one symbol per tape on each side, an action being a symbol
+//! to write, `L` or `R`. Single character symbols can be written next to each other, `ab/Rc`,
+//! otherwise they are separated by commas, `x1,a/R,x0`. With one tape the whole side is
+//! the symbol, `x1/R`. Inputs are a list of symbols when they contain a comma. A comma is
+//! a symbol only when it stands alone, on one tape or as the whole input.
+
+use super::definitions::RSymbol;
+
+pub const SEPARATOR: char = ',';
+pub const DIVIDER: char = '/';
+pub const LEFT: &str = "L";
+pub const RIGHT: &str = "R";
+
+pub fn is_move(symbol: &str) -> bool {
+    symbol == LEFT || symbol == RIGHT
+}
+
+/// Symbols of one side of a label, there have to be `count` of them
+pub fn split_symbols(side: &str, count: usize) -> Result<Vec<RSymbol>, String> {
+    // A lone character is the symbol even when it is the separator or a space
+    if count == 1 && side.chars().count() == 1 {
+        return Ok(vec![side.to_owned()])
+    }
+    let symbols: Vec<RSymbol> = if side.contains(SEPARATOR) {
+        side.split(SEPARATOR).map(|s| s.trim().to_owned()).collect()
+    } else if side.chars().count() == count {
+        side.chars().map(String::from).collect()
+    } else if count == 1 {
+        vec![side.trim().to_owned()]
+    } else {
+        return Err(format!("'{}' has {} characters, separate the symbols with '{}'", side, side.chars().count(), SEPARATOR))
+    };
+    if symbols.iter().any(|s| s.is_empty()) {
+        return Err(format!("'{}' has an empty symbol, '{}' separates the symbols and can't be one of them", side, SEPARATOR))
+    }
+    if symbols.len() != count { return Err(format!("'{}' has {} symbols instead of {}", side, symbols.len(), count)) }
+    Ok(symbols)
+}
+
+/// Splits a label in the symbols read and the actions, one per tape
+pub fn split(label: &str, tapes: usize) -> Result<(Vec<RSymbol>, Vec<RSymbol>), String> {
+    let mut sides = label.split(DIVIDER);
+    let (Some(read), Some(action), None) = (sides.next(), sides.next(), sides.next()) else {
+        return Err(format!("Label '{}' needs exactly one '{}' between the symbols read and the actions", label, DIVIDER))
+    };
+    let context = |e: String| format!("Label '{}' does not fit {} tape(s), {}", label, tapes, e);
+    Ok((split_symbols(read, tapes).map_err(context)?, split_symbols(action, tapes).map_err(context)?))
+}
+
+/// Writes symbols back the shortest way `split_symbols` reads them, which never gives
+/// the separator next to other symbols
+pub fn join(symbols: &[RSymbol]) -> String {
+    if symbols.iter().all(|s| s.chars().count() == 1) {
+        symbols.concat()
+    } else {
+        symbols.join(&SEPARATOR.to_string())
+    }
+}
+
+/// Symbols of an input, one per character unless they are separated by commas
+pub fn split_input(w: &str) -> Result<Vec<RSymbol>, String> {
+    if w.chars().count() == 1 || !w.contains(SEPARATOR) {
+        return Ok(w.chars().map(String::from).collect())
+    }
+    let symbols: Vec<RSymbol> = w.split(SEPARATOR).map(|s| s.trim().to_owned()).collect();
+    if symbols.iter().any(|s| s.is_empty()) {
+        return Err(format!("The input '{}' has an empty symbol, '{}' separates the symbols and can't be one of them", w, SEPARATOR))
+    }
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for (label, tapes) in [("a/R", 1), ("x1/R", 1), (",/L", 1), ("ab/Rc", 2), ("x1,a/R,x0", 2), ("a b/LRc", 3)] {
+            let (read, action) = split(label, tapes).unwrap();
+            assert_eq!(read.len(), tapes);
+            assert_eq!(format!("{}{}{}", join(&read), DIVIDER, join(&action)), label);
+        }
+        assert_eq!(split_input("a,bc, d").unwrap(), ["a", "bc", "d"]);
+        assert_eq!(split_input("abc").unwrap(), ["a", "b", "c"]);
+
+        // The separator is a symbol only on its own
+        assert_eq!(split_input(",").unwrap(), [","]);
+        assert!(split(",a/RR", 2).unwrap_err().contains("can't be one of them"));
+        assert!(split("x1,,/R,R", 2).is_err());
+        assert!(split_input("a,,b").is_err());
+        assert!(split_input("x1, ,a").is_err());
+    }
+
+    #[test]
+    fn unicode_and_named_symbols() {
+        // One character per tape even when it takes several bytes
+        let (read, action) = split("ä□/R□", 2).unwrap();
+        assert_eq!((read, action), (vec!["ä".to_owned(), "□".to_owned()], vec!["R".to_owned(), "□".to_owned()]));
+
+        let (read, action) = split("x1,□,ä/L,y2,R", 3).unwrap();
+        assert_eq!(read, ["x1", "□", "ä"]);
+        assert_eq!(action, ["L", "y2", "R"]);
+        assert!(!is_move(&action[1]) && is_move(&action[2]));
+        assert_eq!(join(&read), "x1,□,ä");
+        assert_eq!(join(&["ä".to_owned(), "□".to_owned(), "b".to_owned()]), "ä□b");
+
+        assert_eq!(split_input("x1,ä,x1").unwrap(), ["x1", "ä", "x1"]);
+        assert_eq!(split_input("ä□").unwrap(), ["ä", "□"]);
+        assert!(split("ä□/R", 2).is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        // Three characters with a separator are two symbols, not a, ',' and b
+        assert!(split("a,b/RRR", 3).is_err());
+        assert!(split("abc/R", 2).is_err());
+        assert!(split("a,/R,R", 2).is_err());
+        assert!(split("a/R/L", 1).is_err());
+        assert!(split("aR", 1).is_err());
+    }
+}
@@ -4,6 +4,8 @@ pub mod tape;
 pub mod turing_machine;
 pub mod helper;
 pub mod computation;
+pub mod busy_beaver;
+pub mod label;
 
 pub use alphabet::Alphabet;
 pub use tape::Tape;
@@ -48,6 +48,26 @@ impl Tape {
         Ok(())
     }
 
+    /// Grows the tape up to `cell` if it can
+    fn reach(&mut self, cell: usize) -> Result<(), ()> {
+        if cell < self.content.len() { return Ok(()) }
+        if !self.extend_on_end { return Err(()) }
+        self.content.resize(cell + 1, BLANK);
+        Ok(())
+    }
+
+    pub fn write_at(&mut self, cell: usize, x: LSymbol) -> Result<(), ()> {
+        self.reach(cell)?;
+        self.content[cell] = x;
+        Ok(())
+    }
+
+    pub fn set_head(&mut self, cell: usize) -> Result<(), ()> {
+        self.reach(cell)?;
+        self.head = cell;
+        Ok(())
+    }
+
     pub fn head_position(&self) -> usize {
         self.head
     }
@@ -63,4 +83,32 @@ impl Tape {
     pub fn does_extend_on_end(&self) -> bool {
         self.extend_on_end
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_stay_within_a_tape_that_does_not_grow() {
+        let mut tape = Tape::with_content(vec![1, 2, 3], false);
+        tape.write_at(2, 4).unwrap();
+        tape.set_head(1).unwrap();
+        assert_eq!(tape.content(), [1, 2, 4]);
+        assert_eq!(tape.read(), 2);
+
+        assert!(tape.write_at(3, 5).is_err());
+        assert!(tape.set_head(7).is_err());
+        assert_eq!(tape.content(), [1, 2, 4]);
+        assert_eq!(tape.head_position(), 1);
+    }
+
+    #[test]
+    fn edits_past_the_end_grow_a_tape_that_can() {
+        let mut tape = Tape::with_content(vec![1], true);
+        tape.write_at(3, 2).unwrap();
+        assert_eq!(tape.content(), [1, BLANK, BLANK, 2]);
+        tape.set_head(5).unwrap();
+        assert_eq!(tape.size(), 6);
+        assert_eq!(tape.read(), BLANK);
+    }
+}